@@ -0,0 +1,190 @@
+//! Lookup and load benchmarks against a synthetic dataset shaped like the real ones: ~100k
+//! disjoint IPv4 blocks spread across a few dozen countries, with gaps in between.
+
+use std::{
+    hint::black_box,
+    io::Write,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ipgeod::providers::{
+    CountryFilter, HerrbischoffProvider, Ip2locationOptions, Ip2locationProvider, LineFormat,
+};
+
+const BLOCK_COUNT: u32 = 100_000;
+/// Each `/24` block is followed by a `/24` gap, so that lookups hit gaps as well.
+const BLOCK_STRIDE: u32 = 512;
+const FIRST_BLOCK: u32 = 0x0100_0000;
+const LOOKUP_COUNT: usize = 4096;
+
+const COUNTRIES: &[&str] = &[
+    "AU", "BR", "CA", "CN", "DE", "ES", "FR", "GB", "HK", "IN", "IT", "JP", "KR", "MX", "NL", "PL",
+    "RU", "SE", "SG", "TW", "UA", "US", "VN", "ZA",
+];
+
+struct Dataset {
+    dir: PathBuf,
+}
+
+impl Dataset {
+    /// Writes the same blocks as both a country-ip-blocks style repository and an IP2Location CSV.
+    fn generate() -> Self {
+        let dir = std::env::temp_dir().join(format!("ipgeod-bench-{}", std::process::id()));
+        let ipv4_dir = dir.join("repo").join("ipv4");
+        std::fs::create_dir_all(&ipv4_dir).unwrap();
+
+        let mut cidr_files = COUNTRIES
+            .iter()
+            .map(|country| {
+                let path = ipv4_dir.join(format!("{}.cidr", country.to_lowercase()));
+                std::io::BufWriter::new(std::fs::File::create(path).unwrap())
+            })
+            .collect::<Vec<_>>();
+        let mut csv_file =
+            std::io::BufWriter::new(std::fs::File::create(dir.join("ip2location.csv")).unwrap());
+
+        let mut rng = Lcg(42);
+        for ind in 0..BLOCK_COUNT {
+            let start = FIRST_BLOCK + ind * BLOCK_STRIDE;
+            let end = start + 255;
+            let country_ind = rng.next() as usize % COUNTRIES.len();
+
+            writeln!(cidr_files[country_ind], "{}/24", Ipv4Addr::from(start)).unwrap();
+            writeln!(
+                csv_file,
+                "\"{}\",\"{}\",\"{}\",\"-\"",
+                start, end, COUNTRIES[country_ind]
+            )
+            .unwrap();
+        }
+
+        for mut file in cidr_files.into_iter() {
+            file.flush().unwrap();
+        }
+        csv_file.flush().unwrap();
+
+        Self { dir }
+    }
+
+    fn repo_path(&self) -> PathBuf {
+        self.dir.join("repo")
+    }
+
+    fn csv_path(&self) -> PathBuf {
+        self.dir.join("ip2location.csv")
+    }
+}
+
+impl Drop for Dataset {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Deterministic pseudo-random numbers, so that runs are comparable.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 32) as u32
+    }
+}
+
+fn load_herrbischoff(repo_path: &Path) -> HerrbischoffProvider {
+    HerrbischoffProvider::from_repo(
+        &[repo_path.to_owned()],
+        &CountryFilter::default(),
+        LineFormat::Cidr,
+    )
+    .unwrap()
+}
+
+fn load_ip2location(csv_path: &Path) -> Ip2locationProvider {
+    Ip2locationProvider::from_db(Some(csv_path), None, &Ip2locationOptions::default()).unwrap()
+}
+
+fn random_addresses() -> Vec<Ipv4Addr> {
+    let covered_len = BLOCK_COUNT * BLOCK_STRIDE;
+    let mut rng = Lcg(7);
+
+    (0..LOOKUP_COUNT)
+        .map(|_| Ipv4Addr::from(FIRST_BLOCK + rng.next() % covered_len))
+        .collect()
+}
+
+fn sequential_addresses() -> Vec<Ipv4Addr> {
+    (0..LOOKUP_COUNT as u32)
+        .map(|ind| Ipv4Addr::from(FIRST_BLOCK + ind * 64))
+        .collect()
+}
+
+fn bench_load(c: &mut Criterion) {
+    let dataset = Dataset::generate();
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+
+    group.bench_function("herrbischoff", |b| {
+        b.iter_batched(
+            || dataset.repo_path(),
+            |repo_path| load_herrbischoff(&repo_path),
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("ip2location", |b| {
+        b.iter_batched(
+            || dataset.csv_path(),
+            |csv_path| load_ip2location(&csv_path),
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let dataset = Dataset::generate();
+    let herrbischoff = load_herrbischoff(&dataset.repo_path());
+    let herrbischoff_lpm = load_herrbischoff(&dataset.repo_path()).with_lpm_index();
+    let ip2location = load_ip2location(&dataset.csv_path());
+
+    for (name, addresses) in [
+        ("random", random_addresses()),
+        ("sequential", sequential_addresses()),
+    ] {
+        let mut group = c.benchmark_group(format!("get_ipv4_country/{}", name));
+        group.throughput(Throughput::Elements(addresses.len() as u64));
+
+        group.bench_function("herrbischoff", |b| {
+            b.iter(|| {
+                for ip_address in addresses.iter() {
+                    black_box(herrbischoff.get_ipv4_country(black_box(ip_address)));
+                }
+            })
+        });
+        group.bench_function("herrbischoff_lpm", |b| {
+            b.iter(|| {
+                for ip_address in addresses.iter() {
+                    black_box(herrbischoff_lpm.get_ipv4_country(black_box(ip_address)));
+                }
+            })
+        });
+        group.bench_function("ip2location", |b| {
+            b.iter(|| {
+                for ip_address in addresses.iter() {
+                    black_box(ip2location.get_ipv4_country(black_box(ip_address)));
+                }
+            })
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_load, bench_lookup);
+criterion_main!(benches);
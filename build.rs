@@ -0,0 +1,23 @@
+use std::process::Command;
+
+fn main() {
+    // Rebuilds when switching branches or committing, without rerunning on every source change
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD") {
+        if let Some(head_ref) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=.git/{}", head_ref);
+        }
+    }
+    println!("cargo:rerun-if-changed=.git/packed-refs");
+
+    // Left unset when building outside a git checkout, e.g. from a crate tarball
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=IPGEOD_GIT_COMMIT={}", commit.trim());
+    }
+}
@@ -0,0 +1,33 @@
+//! Feeds arbitrary bytes into the IP2Location CSV loader, which must reject malformed input with
+//! an error instead of panicking.
+
+#![no_main]
+
+use ipgeod::providers::{Ip2locationOptions, Ip2locationProvider};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for sort in [false, true] {
+        let options = Ip2locationOptions {
+            sort,
+            ..Default::default()
+        };
+
+        if let Ok(provider) = Ip2locationProvider::from_readers(Some(data), None::<&[u8]>, &options)
+        {
+            // Lookups against whatever was accepted must not panic either
+            for ip_address in [0, 1, 0x0102_0304, u32::MAX - 1, u32::MAX] {
+                let _ = provider.get_ipv4_country(&ip_address.into());
+                let _ = provider.get_ipv4_range(&ip_address.into());
+            }
+            let _ = provider.get_ipv4_spans(0, u32::MAX);
+        }
+
+        if let Ok(provider) = Ip2locationProvider::from_readers(None::<&[u8]>, Some(data), &options)
+        {
+            for ip_address in [0, 1, u128::MAX - 1, u128::MAX] {
+                let _ = provider.get_ipv6_country(&ip_address.into());
+            }
+        }
+    }
+});
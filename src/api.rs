@@ -0,0 +1,1637 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use cidr::Ipv4Cidr;
+use clap::ValueEnum;
+use log::info;
+use poem::{
+    handler,
+    http::{header, HeaderValue, StatusCode},
+    web::Data,
+    IntoResponse, Request, Response,
+};
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    registry::{MetaHeader, MetaMediaType, MetaResponse, MetaResponses, Registry},
+    types::{ToJSON, Type},
+    ApiResponse, Enum, Object, OpenApi,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::{
+    asn::AsnDatabase,
+    cache::LookupCache,
+    client_ip,
+    country::{self, CountryCode},
+    metrics::{LookupStatus, Metrics},
+    providers::{CountryMatch, IpgeoProvider, Ipv4Span, LookupOutcome},
+    request_id, telemetry, util,
+};
+
+/// How single-address lookups that find nothing are answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NotFoundStatus {
+    #[value(name = "404")]
+    NotFound,
+    #[value(name = "204")]
+    NoContent,
+    #[value(name = "200")]
+    Ok,
+}
+
+/// Database file or directory as of when it was loaded.
+#[derive(Debug)]
+pub struct DatasetFile {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+/// The active provider along with the files it was loaded from, swapped as a whole on reloads so
+/// that the two never disagree.
+#[derive(Debug)]
+pub struct Dataset {
+    pub provider: IpgeoProvider,
+    pub files: Vec<DatasetFile>,
+}
+
+impl Dataset {
+    /// Modification time of the newest file the dataset was loaded from.
+    pub fn updated_at(&self) -> Option<SystemTime> {
+        self.files.iter().filter_map(|item| item.modified).max()
+    }
+}
+
+/// The lookup endpoints, served through `poem_openapi::OpenApiService`.
+#[derive(Debug, Clone)]
+pub struct Api {
+    dataset: Arc<ArcSwap<Dataset>>,
+    metrics: Arc<Metrics>,
+    cache: Option<Arc<LookupCache>>,
+    asn: Option<Arc<AsnDatabase>>,
+    trust_proxy: bool,
+    not_found_status: NotFoundStatus,
+    max_range_size: u64,
+    default_country: Option<CountryCode>,
+    country_code_alias: bool,
+}
+
+/// Maximum number of IP addresses accepted in a single batch lookup request.
+pub const MAX_BATCH_SIZE: usize = 1000;
+const MIN_CIDR_PREFIX_LEN: u8 = 8;
+const DEFAULT_TOP_COUNTRIES: usize = 10;
+/// Longest prefix accepted by `/ipv4/summary`, which keeps responses to 65536 subnets at most.
+const MAX_SUMMARY_PREFIX_LEN: u8 = 16;
+/// Number of addresses looked up at a time while streaming `/dump/ipv4`.
+const DUMP_CHUNK_SIZE: u32 = 1 << 24;
+/// Response header carrying the modification time of the dataset behind lookup answers.
+pub const X_DATA_UPDATED: &str = "x-data-updated";
+
+#[derive(Debug)]
+pub enum ApiError {
+    InvalidIpAddress,
+    /// Carries the reason for the miss, which is only exposed in debug mode, and the closest
+    /// ranges around the address if requested.
+    IpAddressNotFound {
+        reason: Option<&'static str>,
+        nearest: Option<Box<NearestRanges>>,
+    },
+    BatchTooLarge,
+    InvalidCountryCode,
+    InvalidCidr,
+    CidrTooLarge,
+    AsnNotLoaded,
+    ReservedAddress,
+    InvalidSummaryPrefixLen,
+    InvalidRange,
+    RangeTooLarge {
+        max_size: u64,
+    },
+}
+
+#[derive(Debug, Default, Object)]
+struct ApiErrorResponse {
+    code: u32,
+    message: String,
+    /// Why the address was not found (`in_gap`, `below_range`, `above_range` or `conflict`), only
+    /// included when requested with `debug=true`
+    #[oai(skip_serializing_if_is_none)]
+    outcome: Option<String>,
+    /// Always `false`, only included when requested with `nearest=true`
+    #[oai(skip_serializing_if_is_none)]
+    found: Option<bool>,
+    /// Closest IP2Location range below the address, only included when requested with
+    /// `nearest=true`
+    #[oai(skip_serializing_if_is_none)]
+    below: Option<CountrySpan>,
+    /// Closest IP2Location range above the address, only included when requested with
+    /// `nearest=true`
+    #[oai(skip_serializing_if_is_none)]
+    above: Option<CountrySpan>,
+}
+
+/// The closest covered ranges around an address that isn't covered.
+#[derive(Debug)]
+pub struct NearestRanges {
+    below: Option<CountrySpan>,
+    above: Option<CountrySpan>,
+}
+
+#[derive(Debug, Clone, Copy, Enum)]
+#[oai(rename_all = "lowercase")]
+enum CountryCase {
+    Upper,
+    Lower,
+}
+
+/// Representation of lookup answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+enum LookupFormat {
+    Json,
+    /// The bare country code
+    Text,
+    /// A GeoJSON `Feature` with a `Point` geometry
+    Geojson,
+}
+
+/// GeoJSON `Feature` locating an address, for mapping clients.
+#[derive(Debug, Object)]
+struct GeoJsonFeature {
+    /// Always `Feature`
+    #[oai(rename = "type")]
+    kind: String,
+    /// Location of the address, or `null` if the database has no coordinates for it
+    geometry: Option<GeoJsonPoint>,
+    properties: IpGeolocation,
+}
+
+#[derive(Debug, Object)]
+struct GeoJsonPoint {
+    /// Always `Point`
+    #[oai(rename = "type")]
+    kind: String,
+    /// Longitude and latitude, in that order
+    coordinates: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Object)]
+struct IpGeolocation {
+    /// Two-letter ISO 3166-1 country code, only missing when the server is started with
+    /// `--not-found-status 200` and the address is not found
+    country: Option<String>,
+    /// Same as `country`, only included when the server is started with `--country-code-alias`
+    /// for clients expecting this name
+    #[oai(skip_serializing_if_is_none)]
+    country_code: Option<String>,
+    /// ISO 3166-1 numeric country code, if the code is recognized
+    country_numeric: Option<u16>,
+    /// English name of the country, if the code is recognized
+    country_name: Option<String>,
+    /// Two-letter continent code (AF, AN, AS, EU, NA, OC or SA), if the country is recognized
+    continent: Option<String>,
+    /// English name of the continent, if the country is recognized
+    continent_name: Option<String>,
+    /// Name of the provider that resolved the country
+    source: Option<String>,
+    /// Region name, only available from databases with location data
+    #[oai(skip_serializing_if_is_none)]
+    region: Option<String>,
+    /// City name, only available from databases with location data
+    #[oai(skip_serializing_if_is_none)]
+    city: Option<String>,
+    /// Latitude, only available from databases with location data
+    #[oai(skip_serializing_if_is_none)]
+    latitude: Option<f64>,
+    /// Longitude, only available from databases with location data
+    #[oai(skip_serializing_if_is_none)]
+    longitude: Option<f64>,
+    /// Always `true`, only included when the address is not found and the server answers with
+    /// the `--default-country` instead
+    #[oai(skip_serializing_if_is_none)]
+    is_default: Option<bool>,
+}
+
+#[derive(Debug, Clone, Object)]
+struct BatchResult {
+    ip_address: String,
+    country: Option<String>,
+    error_code: Option<u32>,
+}
+
+#[derive(Debug, Clone, Object)]
+struct CountrySpan {
+    /// Two-letter ISO 3166-1 country code
+    country: String,
+    /// First address of the sub-range assigned to the country
+    start: String,
+    /// Last address of the sub-range assigned to the country
+    end: String,
+}
+
+#[derive(Debug, Object)]
+struct IsCountryResult {
+    /// Whether the address resolves to the country
+    #[oai(rename = "match")]
+    is_match: bool,
+}
+
+#[derive(Debug, Object)]
+struct AsnInfo {
+    /// Autonomous system number
+    asn: u32,
+    /// Name of the organization operating the autonomous system
+    as_name: String,
+}
+
+#[derive(Debug, Object)]
+struct RangeInfo {
+    /// Two-letter ISO 3166-1 country code
+    country: String,
+    /// First address of the range
+    start: String,
+    /// Last address of the range
+    end: String,
+    /// Number of addresses in the range
+    address_count: u64,
+    /// The range in CIDR notation, if it forms a single CIDR block
+    #[oai(skip_serializing_if_is_none)]
+    cidr: Option<String>,
+    /// Name of the provider that resolved the range
+    source: String,
+}
+
+#[derive(Debug, Object)]
+struct DatasetStats {
+    /// Active provider variant
+    provider: String,
+    /// Number of loaded IPv4 ranges or CIDR blocks
+    ipv4_ranges: u64,
+    /// Number of loaded IPv6 ranges or CIDR blocks
+    ipv6_ranges: u64,
+    /// Number of IPv4 addresses mapped to some country
+    ipv4_addresses: u64,
+    /// Percentage of the full IPv4 address space mapped to some country
+    ipv4_coverage_percent: f64,
+    /// Countries with the most IPv4 addresses, in descending order
+    top_countries: Vec<CountryStats>,
+}
+
+#[derive(Debug, Object)]
+struct SubnetSummary {
+    /// Prefix length of the subnets the address space is divided into
+    prefix_len: u8,
+    /// Total number of subnets of the prefix length
+    subnets: u64,
+    /// Number of subnets without any address covered in the dataset
+    uncovered_subnets: u64,
+    /// Number of subnets each country covers the most addresses in, sorted by count
+    countries: Vec<CountrySubnets>,
+}
+
+#[derive(Debug, Object)]
+struct CountrySubnets {
+    /// Two-letter ISO 3166-1 country code
+    country: String,
+    /// Number of subnets where the country covers more addresses than any other
+    subnets: u64,
+}
+
+#[derive(Debug, Object)]
+struct CountryStats {
+    /// Two-letter ISO 3166-1 country code
+    country: String,
+    /// Number of contiguous IPv4 ranges assigned to the country
+    ipv4_ranges: u64,
+    /// Number of IPv4 addresses assigned to the country
+    ipv4_addresses: u64,
+}
+
+/// Lookup result, rendered as JSON by default, or as a bare country code for clients that accept
+/// `text/plain`.
+#[derive(Debug)]
+struct LookupResponse {
+    format: LookupFormat,
+    not_found_status: NotFoundStatus,
+    data_updated: Option<SystemTime>,
+    /// Weak entity tag of the response, only set when the dataset vintage is known
+    etag: Option<String>,
+    /// Whether the client already has the response cached, as told by `If-None-Match`
+    not_modified: bool,
+    result: Result<IpGeolocation, ApiError>,
+}
+
+impl IpGeolocation {
+    fn new(country_match: CountryMatch) -> Self {
+        let country = country::find_country(country_match.country.as_str());
+
+        let location = country_match.location;
+
+        Self {
+            country: Some(country_match.country.to_string()),
+            country_code: None,
+            country_numeric: country.map(|item| item.numeric),
+            country_name: country.map(|item| item.name.to_owned()),
+            continent: country.map(|item| item.continent.code().to_owned()),
+            continent_name: country.map(|item| item.continent.name().to_owned()),
+            source: Some(country_match.source.to_owned()),
+            region: location.as_ref().and_then(|item| item.region.clone()),
+            city: location.as_ref().and_then(|item| item.city.clone()),
+            latitude: location.as_ref().map(|item| item.latitude),
+            longitude: location.as_ref().map(|item| item.longitude),
+            is_default: None,
+        }
+    }
+}
+
+impl Api {
+    pub fn new(
+        dataset: Arc<ArcSwap<Dataset>>,
+        metrics: Arc<Metrics>,
+        cache: Option<Arc<LookupCache>>,
+        asn: Option<Arc<AsnDatabase>>,
+        trust_proxy: bool,
+        not_found_status: NotFoundStatus,
+        max_range_size: u64,
+    ) -> Self {
+        Self {
+            dataset,
+            metrics,
+            cache,
+            asn,
+            trust_proxy,
+            not_found_status,
+            max_range_size,
+            default_country: None,
+            country_code_alias: false,
+        }
+    }
+
+    /// Answers single-address lookups that find nothing with the country instead.
+    pub fn with_default_country(mut self, default_country: Option<CountryCode>) -> Self {
+        self.default_country = default_country;
+        self
+    }
+
+    /// Duplicates `country` as `country_code` in single-address lookup responses.
+    pub fn with_country_code_alias(mut self, country_code_alias: bool) -> Self {
+        self.country_code_alias = country_code_alias;
+        self
+    }
+
+    /// Wraps up a lookup result. `ip_address` is the queried address, if valid, which responses are
+    /// tagged by for conditional requests along with the dataset vintage.
+    fn lookup_response(
+        &self,
+        req: &Request,
+        ip_address: Option<IpAddr>,
+        result: Result<IpGeolocation, ApiError>,
+        debug: bool,
+        format: Option<LookupFormat>,
+    ) -> LookupResponse {
+        let geolocation = result.as_ref().ok();
+        telemetry::record_lookup(
+            request_id::request_id(req),
+            ip_address,
+            geolocation.and_then(|item| item.country.as_deref()),
+            geolocation.and_then(|item| item.source.as_deref()),
+            lookup_status(&result).as_str(),
+        );
+
+        let format = format.unwrap_or(if accepts_plain_text(req) {
+            LookupFormat::Text
+        } else {
+            LookupFormat::Json
+        });
+        let data_updated = self.dataset.load().updated_at();
+
+        let etag = data_updated
+            .zip(ip_address)
+            .map(|(data_updated, ip_address)| lookup_etag(data_updated, ip_address, req, format));
+        let not_modified = etag.as_ref().is_some_and(|etag| {
+            req.headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| etag_matches(value, etag))
+        });
+
+        let result = match (result, self.default_country) {
+            (Err(ApiError::IpAddressNotFound { .. }), Some(default_country)) => {
+                Ok(IpGeolocation::default_country(default_country))
+            }
+            (result, _) => result.map_err(|err| err.debug(debug)),
+        };
+        let result = match self.country_code_alias {
+            true => result.map(|geolocation| IpGeolocation {
+                country_code: geolocation.country.clone(),
+                ..geolocation
+            }),
+            false => result,
+        };
+
+        LookupResponse {
+            format,
+            not_found_status: self.not_found_status,
+            data_updated,
+            etag,
+            not_modified,
+            result,
+        }
+    }
+
+    fn lookup_ipv4(&self, ip_address: &str) -> Result<IpGeolocation, ApiError> {
+        let ip_address = Ipv4Addr::from_str(ip_address).map_err(|_| ApiError::InvalidIpAddress)?;
+
+        self.lookup_ipv4_addr(&ip_address)
+    }
+
+    fn lookup_ipv4_addr(&self, ip_address: &Ipv4Addr) -> Result<IpGeolocation, ApiError> {
+        if util::is_reserved_ipv4(ip_address) {
+            return Err(ApiError::ReservedAddress);
+        }
+
+        let country_match = match &self.cache {
+            Some(cache) => {
+                let (country_match, hit) = cache.get_or_insert_with(u32::from(*ip_address), || {
+                    self.dataset.load().provider.match_ipv4(ip_address)
+                });
+                self.metrics.observe_cache_lookup(hit);
+                country_match
+            }
+            None => self.dataset.load().provider.match_ipv4(ip_address),
+        };
+
+        geolocation_result(country_match)
+    }
+
+    fn batch_result(&self, ip_address: String) -> BatchResult {
+        match self.lookup_ipv4(&ip_address) {
+            Ok(geolocation) => BatchResult {
+                ip_address,
+                country: geolocation.country,
+                error_code: None,
+            },
+            Err(err) => BatchResult {
+                ip_address,
+                country: None,
+                error_code: Some(err.code()),
+            },
+        }
+    }
+
+    fn nearest_ranges(&self, ip_address: &Ipv4Addr) -> NearestRanges {
+        let (below, above) = self.dataset.load().provider.get_ipv4_neighbors(ip_address);
+        let country_span = |span: Ipv4Span| CountrySpan {
+            country: span.country.to_string(),
+            start: Ipv4Addr::from(span.start).to_string(),
+            end: Ipv4Addr::from(span.end).to_string(),
+        };
+
+        NearestRanges {
+            below: below.map(country_span),
+            above: above.map(country_span),
+        }
+    }
+
+    fn observe_lookup(&self, result: &Result<IpGeolocation, ApiError>, started_at: Instant) {
+        self.metrics
+            .observe_lookup(lookup_status(result), started_at.elapsed());
+    }
+
+    /// Emits a structured event for a single-address lookup.
+    fn log_lookup(
+        &self,
+        req: &Request,
+        result: &Result<IpGeolocation, ApiError>,
+        started_at: Instant,
+    ) {
+        let client_ip = client_ip::client_ip(req, self.trust_proxy).map(|ip| ip.to_string());
+        let country = result
+            .as_ref()
+            .ok()
+            .and_then(|item| item.country.as_deref());
+        let latency_us = started_at.elapsed().as_micros() as u64;
+        let request_id = request_id::request_id(req);
+
+        info!(
+            target: "ipgeod::lookup",
+            path = req.uri().path(),
+            client_ip = client_ip.as_deref(),
+            country = country,
+            latency_us = latency_us,
+            request_id = request_id;
+            "{} from {} resolved to {} in {}us (request {})",
+            req.uri().path(),
+            client_ip.as_deref().unwrap_or("unknown"),
+            country.unwrap_or("nothing"),
+            latency_us,
+            request_id.unwrap_or("unknown")
+        );
+    }
+}
+
+#[OpenApi]
+impl Api {
+    #[oai(path = "/ipv4/:ip_address", method = "get")]
+    /// Gets the two-letter ISO 3166 country code associated with the IPv4 address
+    ///
+    /// Responds with the bare country code instead of JSON when the `Accept` header asks for
+    /// `text/plain`, or with a GeoJSON `Feature` when requested with `format=geojson`.
+    async fn get_ipv4(
+        &self,
+        ip_address: Path<String>,
+        /// Include the reason in the error response when the address is not found
+        debug: Query<Option<bool>>,
+        /// Include the closest IP2Location ranges below and above the address in the error
+        /// response when the address is not found
+        nearest: Query<Option<bool>>,
+        /// Letter case of the returned country code (defaults to `upper`)
+        case: Query<Option<CountryCase>>,
+        /// Representation of the answer, overriding the `Accept` header. `geojson` locates the
+        /// address with a `Point` geometry, which is `null` if the database has no coordinates.
+        format: Query<Option<LookupFormat>>,
+        req: &Request,
+    ) -> LookupResponse {
+        let started_at = Instant::now();
+        let result = self.lookup_ipv4(&ip_address.0);
+        self.observe_lookup(&result, started_at);
+        self.log_lookup(req, &result, started_at);
+
+        let result = match result {
+            Err(ApiError::IpAddressNotFound { reason, .. }) if nearest.0.unwrap_or(false) => {
+                Err(ApiError::IpAddressNotFound {
+                    reason,
+                    nearest: Ipv4Addr::from_str(&ip_address.0)
+                        .ok()
+                        .map(|ip_address| Box::new(self.nearest_ranges(&ip_address))),
+                })
+            }
+            result => result,
+        };
+
+        let mut response = self.lookup_response(
+            req,
+            Ipv4Addr::from_str(&ip_address.0).ok().map(IpAddr::V4),
+            result,
+            debug.0.unwrap_or(false),
+            format.0,
+        );
+
+        // Applied last so that `--default-country` answers are lowercased as well
+        response.result = match case.0.unwrap_or(CountryCase::Upper) {
+            CountryCase::Upper => response.result,
+            CountryCase::Lower => response.result.map(IpGeolocation::lowercase),
+        };
+
+        response
+    }
+
+    #[oai(path = "/ipv4/int/:value", method = "get")]
+    /// Gets the two-letter ISO 3166 country code associated with the IPv4 address given as a
+    /// decimal 32-bit integer, e.g. `/ipv4/int/16909060` for `1.2.3.4`
+    ///
+    /// Responds with the bare country code instead of JSON when the `Accept` header asks for
+    /// `text/plain`.
+    async fn get_ipv4_int(
+        &self,
+        value: Path<String>,
+        /// Include the reason in the error response when the address is not found
+        debug: Query<Option<bool>>,
+        req: &Request,
+    ) -> LookupResponse {
+        let started_at = Instant::now();
+        let ip_address = u32::from_str(&value.0).ok().map(Ipv4Addr::from);
+        let result = match &ip_address {
+            Some(ip_address) => self.lookup_ipv4_addr(ip_address),
+            None => Err(ApiError::InvalidIpAddress),
+        };
+        self.observe_lookup(&result, started_at);
+        self.log_lookup(req, &result, started_at);
+
+        self.lookup_response(
+            req,
+            ip_address.map(IpAddr::V4),
+            result,
+            debug.0.unwrap_or(false),
+            None,
+        )
+    }
+
+    #[oai(path = "/ipv4", method = "get")]
+    /// Gets the two-letter ISO 3166 country code associated with the IPv4 address of the client
+    ///
+    /// The `X-Forwarded-For` and `X-Real-IP` headers are only honored when the server is started
+    /// with `--trust-proxy`.
+    async fn get_client_ipv4(
+        &self,
+        /// Include the reason in the error response when the address is not found
+        debug: Query<Option<bool>>,
+        req: &Request,
+    ) -> LookupResponse {
+        let started_at = Instant::now();
+        let client_ip = client_ip::client_ip(req, self.trust_proxy);
+        let result = match client_ip {
+            Some(IpAddr::V4(ip_address)) => self.lookup_ipv4_addr(&ip_address),
+            Some(IpAddr::V6(ip_address)) => match ip_address.to_ipv4_mapped() {
+                Some(ip_address) => self.lookup_ipv4_addr(&ip_address),
+                None => Err(ApiError::InvalidIpAddress),
+            },
+            None => Err(ApiError::InvalidIpAddress),
+        };
+        self.observe_lookup(&result, started_at);
+        self.log_lookup(req, &result, started_at);
+
+        self.lookup_response(req, client_ip, result, debug.0.unwrap_or(false), None)
+    }
+
+    #[oai(path = "/ipv4/batch", method = "post")]
+    /// Gets the two-letter ISO 3166 country codes associated with a list of IPv4 addresses
+    ///
+    /// Failed lookups are reported individually via `error_code` instead of failing the whole
+    /// request.
+    async fn post_ipv4_batch(
+        &self,
+        ip_addresses: Json<Vec<String>>,
+    ) -> Result<Json<Vec<BatchResult>>, ApiError> {
+        if ip_addresses.0.len() > MAX_BATCH_SIZE {
+            return Err(ApiError::BatchTooLarge);
+        }
+
+        let results = ip_addresses
+            .0
+            .into_iter()
+            .map(|ip_address| self.batch_result(ip_address))
+            .collect();
+
+        Ok(Json(results))
+    }
+
+    #[oai(path = "/ipv4/:ip_address/all", method = "get")]
+    /// Lists every distinct country code the configured providers associate with the IPv4
+    /// address, in the order the providers are tried
+    ///
+    /// Unlike `/ipv4/{ip_address}`, disagreements between providers are not resolved. An empty
+    /// list means that no provider covers the address.
+    async fn get_ipv4_all(&self, ip_address: Path<String>) -> Result<Json<Vec<String>>, ApiError> {
+        let ip_address =
+            Ipv4Addr::from_str(&ip_address.0).map_err(|_| ApiError::InvalidIpAddress)?;
+        if util::is_reserved_ipv4(&ip_address) {
+            return Err(ApiError::ReservedAddress);
+        }
+
+        Ok(Json(
+            self.dataset
+                .load()
+                .provider
+                .get_ipv4_countries(&ip_address)
+                .iter()
+                .map(CountryCode::to_string)
+                .collect(),
+        ))
+    }
+
+    #[oai(path = "/ipv4/:ip_address/is/:country", method = "get")]
+    /// Checks whether the IPv4 address resolves to the two-letter ISO 3166 country code, compared
+    /// case-insensitively
+    ///
+    /// Addresses that aren't found, including reserved ones, never match. `--default-country` is
+    /// not applied.
+    async fn get_ipv4_is_country(
+        &self,
+        ip_address: Path<String>,
+        country: Path<String>,
+    ) -> Result<Json<IsCountryResult>, ApiError> {
+        let country = CountryCode::new(&country.0).ok_or(ApiError::InvalidCountryCode)?;
+
+        let is_match = match self.lookup_ipv4(&ip_address.0) {
+            Ok(geolocation) => geolocation.country.as_deref() == Some(country.as_str()),
+            Err(ApiError::IpAddressNotFound { .. } | ApiError::ReservedAddress) => false,
+            Err(err) => return Err(err),
+        };
+
+        Ok(Json(IsCountryResult { is_match }))
+    }
+
+    #[oai(path = "/ipv4/:ip_address/asn", method = "get")]
+    /// Gets the autonomous system the IPv4 address belongs to
+    ///
+    /// Only available when the server is started with `--ip2location-asn`.
+    async fn get_ipv4_asn(&self, ip_address: Path<String>) -> Result<Json<AsnInfo>, ApiError> {
+        let asn = self.asn.as_ref().ok_or(ApiError::AsnNotLoaded)?;
+        let ip_address =
+            Ipv4Addr::from_str(&ip_address.0).map_err(|_| ApiError::InvalidIpAddress)?;
+
+        match asn.get_ipv4_asn(&ip_address) {
+            Some(record) => Ok(Json(AsnInfo {
+                asn: record.asn,
+                as_name: record.as_name,
+            })),
+            None => Err(ApiError::IpAddressNotFound {
+                reason: None,
+                nearest: None,
+            }),
+        }
+    }
+
+    #[oai(path = "/ipv4/:ip_address/range", method = "get")]
+    /// Gets the range the IPv4 address was matched in, as stored in the database
+    async fn get_ipv4_range(
+        &self,
+        ip_address: Path<String>,
+        /// Includes the reason for a miss in `outcome` when set
+        debug: Query<Option<bool>>,
+    ) -> Result<Json<RangeInfo>, ApiError> {
+        let ip_address =
+            Ipv4Addr::from_str(&ip_address.0).map_err(|_| ApiError::InvalidIpAddress)?;
+        if util::is_reserved_ipv4(&ip_address) {
+            return Err(ApiError::ReservedAddress);
+        }
+
+        match self.dataset.load().provider.get_ipv4_range(&ip_address) {
+            LookupOutcome::Found(range_match) => {
+                let span = range_match.span;
+                let cidrs = util::range_to_cidrs(span.start, span.end);
+
+                Ok(Json(RangeInfo {
+                    country: span.country.to_string(),
+                    start: Ipv4Addr::from(span.start).to_string(),
+                    end: Ipv4Addr::from(span.end).to_string(),
+                    address_count: (span.end - span.start) as u64 + 1,
+                    cidr: match cidrs.as_slice() {
+                        // Alternate formatting keeps the `/32` suffix on single addresses
+                        [cidr] => Some(format!("{:#}", cidr)),
+                        _ => None,
+                    },
+                    source: range_match.source.to_owned(),
+                }))
+            }
+            outcome => Err(ApiError::IpAddressNotFound {
+                reason: outcome.miss_reason(),
+                nearest: None,
+            }
+            .debug(debug.0.unwrap_or(false))),
+        }
+    }
+
+    #[oai(path = "/stats", method = "get")]
+    /// Summarizes how much of the address space the loaded dataset covers
+    async fn get_stats(
+        &self,
+        /// Number of countries to include in `top_countries` (defaults to 10)
+        top: Query<Option<usize>>,
+    ) -> Json<DatasetStats> {
+        let dataset = self.dataset.load();
+        let provider = &dataset.provider;
+
+        let mut countries: HashMap<CountryCode, CountryStats> = HashMap::new();
+        let mut ipv4_addresses = 0;
+        for span in provider.get_ipv4_spans(0, u32::MAX) {
+            let addresses = (span.end - span.start) as u64 + 1;
+            ipv4_addresses += addresses;
+
+            let stats = countries
+                .entry(span.country)
+                .or_insert_with_key(|country| CountryStats {
+                    country: country.to_string(),
+                    ipv4_ranges: 0,
+                    ipv4_addresses: 0,
+                });
+            stats.ipv4_ranges += 1;
+            stats.ipv4_addresses += addresses;
+        }
+
+        let mut top_countries = countries.into_values().collect::<Vec<_>>();
+        top_countries.sort_unstable_by(|a, b| {
+            b.ipv4_addresses
+                .cmp(&a.ipv4_addresses)
+                .then_with(|| a.country.cmp(&b.country))
+        });
+        top_countries.truncate(top.0.unwrap_or(DEFAULT_TOP_COUNTRIES));
+
+        Json(DatasetStats {
+            provider: provider.name().to_owned(),
+            ipv4_ranges: provider.ipv4_range_count() as u64,
+            ipv6_ranges: provider.ipv6_range_count() as u64,
+            ipv4_addresses,
+            ipv4_coverage_percent: ipv4_addresses as f64 / (1u64 << 32) as f64 * 100.0,
+            top_countries,
+        })
+    }
+
+    #[oai(path = "/ipv4/summary/:prefix_len", method = "get")]
+    /// Counts the IPv4 subnets of the prefix length mapping predominantly to each country, e.g.
+    /// `/ipv4/summary/8`
+    ///
+    /// Each subnet is attributed to the country covering the most addresses in it, with ties going
+    /// to the alphabetically first country code. Prefixes longer than `/16` are rejected.
+    async fn get_ipv4_summary(
+        &self,
+        prefix_len: Path<String>,
+    ) -> Result<Json<SubnetSummary>, ApiError> {
+        let prefix_len = u8::from_str(&prefix_len.0)
+            .ok()
+            .filter(|prefix_len| *prefix_len <= MAX_SUMMARY_PREFIX_LEN)
+            .ok_or(ApiError::InvalidSummaryPrefixLen)?;
+
+        let spans = self.dataset.load().provider.get_ipv4_spans(0, u32::MAX);
+        let predominant = predominant_countries(&spans, prefix_len);
+
+        let subnets = 1u64 << prefix_len;
+        let mut countries: HashMap<CountryCode, u64> = HashMap::new();
+        for country in predominant.iter() {
+            *countries.entry(*country).or_default() += 1;
+        }
+
+        let mut countries = countries
+            .into_iter()
+            .map(|(country, subnets)| CountrySubnets {
+                country: country.to_string(),
+                subnets,
+            })
+            .collect::<Vec<_>>();
+        countries.sort_unstable_by(|a, b| {
+            b.subnets
+                .cmp(&a.subnets)
+                .then_with(|| a.country.cmp(&b.country))
+        });
+
+        Ok(Json(SubnetSummary {
+            prefix_len,
+            subnets,
+            uncovered_subnets: subnets - predominant.len() as u64,
+            countries,
+        }))
+    }
+
+    #[oai(path = "/countries", method = "get")]
+    /// Lists the two-letter ISO 3166 codes of all countries present in the loaded dataset, sorted
+    /// alphabetically
+    async fn get_countries(&self) -> Json<Vec<String>> {
+        Json(
+            self.dataset
+                .load()
+                .provider
+                .country_codes()
+                .into_iter()
+                .map(|country| country.to_string())
+                .collect(),
+        )
+    }
+
+    #[oai(path = "/country/:code/ipv4", method = "get")]
+    /// Lists the IPv4 CIDR blocks assigned to the two-letter ISO 3166 country code
+    async fn get_country_ipv4(&self, code: Path<String>) -> Result<Json<Vec<String>>, ApiError> {
+        let code = CountryCode::new(&code.0).ok_or(ApiError::InvalidCountryCode)?;
+
+        let cidrs = self
+            .dataset
+            .load()
+            .provider
+            .get_ipv4_cidrs(code)
+            .into_iter()
+            // Alternate formatting keeps the `/32` suffix on single addresses
+            .map(|cidr| format!("{:#}", cidr))
+            .collect();
+
+        Ok(Json(cidrs))
+    }
+
+    #[oai(path = "/ipv4/cidr/:address/:prefix_len", method = "get")]
+    /// Lists the countries assigned within the IPv4 CIDR block, e.g. `/ipv4/cidr/1.2.3.0/24`
+    ///
+    /// Each entry covers the part of the queried block assigned to the country. Addresses not
+    /// covered in the database are omitted. Prefixes shorter than `/8` are rejected.
+    async fn get_ipv4_cidr(
+        &self,
+        address: Path<String>,
+        prefix_len: Path<String>,
+    ) -> Result<Json<Vec<CountrySpan>>, ApiError> {
+        let cidr = Ipv4Cidr::from_str(&format!("{}/{}", address.0, prefix_len.0))
+            .map_err(|_| ApiError::InvalidCidr)?;
+        if cidr.network_length() < MIN_CIDR_PREFIX_LEN {
+            return Err(ApiError::CidrTooLarge);
+        }
+
+        let spans = self
+            .dataset
+            .load()
+            .provider
+            .get_ipv4_spans(
+                u32::from(cidr.first_address()),
+                u32::from(cidr.last_address()),
+            )
+            .into_iter()
+            .map(|span| CountrySpan {
+                country: span.country.to_string(),
+                start: Ipv4Addr::from(span.start).to_string(),
+                end: Ipv4Addr::from(span.end).to_string(),
+            })
+            .collect();
+
+        Ok(Json(spans))
+    }
+
+    #[oai(path = "/ipv4/range/:start/:end", method = "get")]
+    /// Lists the countries assigned within the inclusive IPv4 address range, e.g.
+    /// `/ipv4/range/1.2.3.0/1.2.4.127`
+    ///
+    /// Each entry covers the part of the queried range assigned to the country. Addresses not
+    /// covered in the database are omitted. Ranges of more addresses than `--max-range-size` are
+    /// rejected.
+    async fn get_ipv4_range_countries(
+        &self,
+        start: Path<String>,
+        end: Path<String>,
+    ) -> Result<Json<Vec<CountrySpan>>, ApiError> {
+        let start = Ipv4Addr::from_str(&start.0).map_err(|_| ApiError::InvalidIpAddress)?;
+        let end = Ipv4Addr::from_str(&end.0).map_err(|_| ApiError::InvalidIpAddress)?;
+        if start > end {
+            return Err(ApiError::InvalidRange);
+        }
+        if u64::from(u32::from(end) - u32::from(start)) + 1 > self.max_range_size {
+            return Err(ApiError::RangeTooLarge {
+                max_size: self.max_range_size,
+            });
+        }
+
+        let spans = self
+            .dataset
+            .load()
+            .provider
+            .get_ipv4_spans(u32::from(start), u32::from(end))
+            .into_iter()
+            .map(|span| CountrySpan {
+                country: span.country.to_string(),
+                start: Ipv4Addr::from(span.start).to_string(),
+                end: Ipv4Addr::from(span.end).to_string(),
+            })
+            .collect();
+
+        Ok(Json(spans))
+    }
+
+    #[oai(path = "/ipv6/:ip_address", method = "get")]
+    /// Gets the two-letter ISO 3166 country code associated with the IPv6 address
+    ///
+    /// IPv4-mapped (`::ffff:1.2.3.4`) and IPv4-compatible (`::1.2.3.4`) addresses are looked up as
+    /// the IPv4 addresses they embed.
+    async fn get_ipv6(
+        &self,
+        ip_address: Path<String>,
+        /// Include the reason in the error response when the address is not found
+        debug: Query<Option<bool>>,
+        req: &Request,
+    ) -> LookupResponse {
+        let started_at = Instant::now();
+        let ip_address = Ipv6Addr::from_str(&ip_address.0);
+        let result = match ip_address {
+            Ok(ip_address) => match util::embedded_ipv4(&ip_address) {
+                Some(ip_address) => self.lookup_ipv4_addr(&ip_address),
+                None if util::is_reserved_ipv6(&ip_address) => Err(ApiError::ReservedAddress),
+                None => geolocation_result(self.dataset.load().provider.match_ipv6(&ip_address)),
+            },
+            Err(_) => Err(ApiError::InvalidIpAddress),
+        };
+        self.log_lookup(req, &result, started_at);
+
+        self.lookup_response(
+            req,
+            ip_address.ok().map(IpAddr::V6),
+            result,
+            debug.0.unwrap_or(false),
+            None,
+        )
+    }
+}
+
+impl ApiError {
+    /// Drops the debugging details unless `enabled` is set.
+    pub fn debug(self, enabled: bool) -> Self {
+        match self {
+            Self::IpAddressNotFound { nearest, .. } if !enabled => Self::IpAddressNotFound {
+                reason: None,
+                nearest,
+            },
+            _ => self,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.describe().0
+    }
+
+    /// Numeric code of the error, as reported in `code` and batch `error_code` fields.
+    pub fn code(&self) -> u32 {
+        self.describe().1
+    }
+
+    /// Catalog of how each error is presented to clients: the HTTP status, the numeric code, and
+    /// the message. Codes are part of the API contract, so they must never change or be reused
+    /// once released. New variants take the next free code.
+    fn describe(&self) -> (StatusCode, u32, Cow<'static, str>) {
+        match self {
+            Self::InvalidIpAddress => (StatusCode::BAD_REQUEST, 100, "Invalid IP address".into()),
+            Self::IpAddressNotFound { .. } => (
+                StatusCode::NOT_FOUND,
+                101,
+                "IP address not covered in database".into(),
+            ),
+            Self::BatchTooLarge => (
+                StatusCode::BAD_REQUEST,
+                102,
+                format!("Batch size exceeds the limit of {}", MAX_BATCH_SIZE).into(),
+            ),
+            Self::InvalidCountryCode => {
+                (StatusCode::BAD_REQUEST, 103, "Invalid country code".into())
+            }
+            Self::InvalidCidr => (StatusCode::BAD_REQUEST, 104, "Invalid CIDR block".into()),
+            Self::CidrTooLarge => (
+                StatusCode::BAD_REQUEST,
+                105,
+                format!("CIDR prefix must be at least /{}", MIN_CIDR_PREFIX_LEN).into(),
+            ),
+            Self::AsnNotLoaded => (StatusCode::NOT_FOUND, 106, "ASN database not loaded".into()),
+            Self::ReservedAddress => (
+                StatusCode::NOT_FOUND,
+                107,
+                "IP address is reserved for special use and not publicly routable".into(),
+            ),
+            Self::InvalidSummaryPrefixLen => (
+                StatusCode::BAD_REQUEST,
+                108,
+                format!(
+                    "Summary prefix length must be between /0 and /{}",
+                    MAX_SUMMARY_PREFIX_LEN
+                )
+                .into(),
+            ),
+            Self::InvalidRange => (
+                StatusCode::BAD_REQUEST,
+                109,
+                "Range start must not be after its end".into(),
+            ),
+            Self::RangeTooLarge { max_size } => (
+                StatusCode::BAD_REQUEST,
+                110,
+                format!("Range exceeds the limit of {} addresses", max_size).into(),
+            ),
+        }
+    }
+}
+
+/// Finds the country covering the most addresses in each subnet of the prefix length that has any
+/// address covered, given spans sorted by address. Ties go to the alphabetically first country.
+fn predominant_countries(spans: &[Ipv4Span], prefix_len: u8) -> Vec<CountryCode> {
+    let subnet_size = 1u64 << (32 - prefix_len);
+
+    let mut predominant = vec![];
+    let mut current_subnet = None;
+    let mut counts: HashMap<CountryCode, u64> = HashMap::new();
+    let mut flush = |counts: &mut HashMap<CountryCode, u64>| {
+        if let Some((country, _)) = counts
+            .drain()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        {
+            predominant.push(country);
+        }
+    };
+
+    for span in spans.iter() {
+        let mut start = span.start as u64;
+        let end = span.end as u64;
+
+        // Spans can cross subnet boundaries, so they're split up by subnet
+        while start <= end {
+            let subnet = start / subnet_size;
+            let subnet_end = end.min((subnet + 1) * subnet_size - 1);
+
+            if current_subnet != Some(subnet) {
+                flush(&mut counts);
+                current_subnet = Some(subnet);
+            }
+            *counts.entry(span.country).or_default() += subnet_end - start + 1;
+
+            start = subnet_end + 1;
+        }
+    }
+    flush(&mut counts);
+
+    predominant
+}
+
+fn lookup_status(result: &Result<IpGeolocation, ApiError>) -> LookupStatus {
+    match result {
+        Ok(_) => LookupStatus::Found,
+        Err(ApiError::InvalidIpAddress) => LookupStatus::Invalid,
+        Err(_) => LookupStatus::NotFound,
+    }
+}
+
+/// Turns a provider lookup outcome into the API result, keeping the miss reason for debugging.
+fn geolocation_result(outcome: LookupOutcome) -> Result<IpGeolocation, ApiError> {
+    match outcome {
+        LookupOutcome::Found(country_match) => Ok(IpGeolocation::new(country_match)),
+        outcome => Err(ApiError::IpAddressNotFound {
+            reason: outcome.miss_reason(),
+            nearest: None,
+        }),
+    }
+}
+
+impl IpGeolocation {
+    /// Lowercases the country code, e.g. for use as a country code top-level domain.
+    fn lowercase(mut self) -> Self {
+        self.country = self.country.map(|country| country.to_lowercase());
+        self.country_code = self.country_code.map(|country| country.to_lowercase());
+        self
+    }
+
+    /// Stands in for a missing address when not-found results are rendered as success.
+    fn empty() -> Self {
+        Self {
+            country: None,
+            country_code: None,
+            country_numeric: None,
+            country_name: None,
+            continent: None,
+            continent_name: None,
+            source: None,
+            region: None,
+            city: None,
+            latitude: None,
+            longitude: None,
+            is_default: None,
+        }
+    }
+
+    /// Stands in for a missing address when `--default-country` is set.
+    fn default_country(country: CountryCode) -> Self {
+        let info = country::find_country(country.as_str());
+
+        Self {
+            country: Some(country.to_string()),
+            country_numeric: info.map(|item| item.numeric),
+            country_name: info.map(|item| item.name.to_owned()),
+            continent: info.map(|item| item.continent.code().to_owned()),
+            continent_name: info.map(|item| item.continent.name().to_owned()),
+            is_default: Some(true),
+            ..Self::empty()
+        }
+    }
+}
+
+impl From<IpGeolocation> for GeoJsonFeature {
+    fn from(geolocation: IpGeolocation) -> Self {
+        Self {
+            kind: "Feature".to_owned(),
+            geometry: geolocation.longitude.zip(geolocation.latitude).map(
+                |(longitude, latitude)| GeoJsonPoint {
+                    kind: "Point".to_owned(),
+                    coordinates: vec![longitude, latitude],
+                },
+            ),
+            properties: geolocation,
+        }
+    }
+}
+
+impl IntoResponse for LookupResponse {
+    fn into_response(self) -> Response {
+        let data_updated = self.data_updated;
+        let etag = self.etag.clone();
+        let not_modified = self.not_modified;
+
+        let mut resp = self.into_response_body();
+        // Conditional requests only apply to successful responses, which are the ones depending on
+        // the dataset alone
+        if let Some(etag) = etag.filter(|_| resp.status().is_success()) {
+            if not_modified {
+                resp = StatusCode::NOT_MODIFIED.into_response();
+            }
+            if let Ok(value) = HeaderValue::from_str(&etag) {
+                resp.headers_mut().insert(header::ETAG, value);
+            }
+        }
+        if let Some(data_updated) = data_updated {
+            if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(data_updated)) {
+                resp.headers_mut().insert(X_DATA_UPDATED, value);
+            }
+        }
+        resp
+    }
+}
+
+impl LookupResponse {
+    fn into_response_body(self) -> Response {
+        let result = match (self.result, self.not_found_status) {
+            (Err(ApiError::IpAddressNotFound { .. }), NotFoundStatus::NoContent) => {
+                return StatusCode::NO_CONTENT.into_response();
+            }
+            (Err(ApiError::IpAddressNotFound { .. }), NotFoundStatus::Ok) => {
+                Ok(IpGeolocation::empty())
+            }
+            (result, _) => result,
+        };
+
+        match (result, self.format) {
+            (Ok(geolocation), LookupFormat::Json) => Json(geolocation).into_response(),
+            (Ok(geolocation), LookupFormat::Text) => Response::builder()
+                .content_type("text/plain; charset=utf-8")
+                .body(format!("{}\n", geolocation.country.unwrap_or_default())),
+            (Ok(geolocation), LookupFormat::Geojson) => Response::builder()
+                .content_type("application/geo+json")
+                .body(GeoJsonFeature::from(geolocation).to_json_string()),
+            (Err(err), LookupFormat::Json | LookupFormat::Geojson) => {
+                poem::Error::from(err).into_response()
+            }
+            (Err(err), LookupFormat::Text) => {
+                let status_code = err.status_code();
+                let response: ApiErrorResponse = err.into();
+
+                Response::builder()
+                    .status(status_code)
+                    .content_type("text/plain; charset=utf-8")
+                    .body(format!("{}\n", response.message))
+            }
+        }
+    }
+}
+
+impl ApiResponse for LookupResponse {
+    fn meta() -> MetaResponses {
+        let mut responses = vec![MetaResponse {
+            description: "",
+            status: Some(200),
+            content: vec![
+                MetaMediaType {
+                    content_type: "application/json",
+                    schema: IpGeolocation::schema_ref(),
+                },
+                MetaMediaType {
+                    content_type: "text/plain",
+                    schema: String::schema_ref(),
+                },
+                MetaMediaType {
+                    content_type: "application/geo+json",
+                    schema: GeoJsonFeature::schema_ref(),
+                },
+            ],
+            headers: vec![
+                MetaHeader {
+                    name: X_DATA_UPDATED.to_owned(),
+                    description: Some(
+                        "Modification time of the newest database file the answer is based on"
+                            .to_owned(),
+                    ),
+                    required: false,
+                    deprecated: false,
+                    schema: String::schema_ref(),
+                },
+                MetaHeader {
+                    name: "ETag".to_owned(),
+                    description: Some(
+                        "Weak entity tag of the answer, changing whenever the dataset is reloaded"
+                            .to_owned(),
+                    ),
+                    required: false,
+                    deprecated: false,
+                    schema: String::schema_ref(),
+                },
+            ],
+        }];
+        responses.push(MetaResponse {
+            description: "Address not found, when started with `--not-found-status 204`",
+            status: Some(204),
+            content: vec![],
+            headers: vec![],
+        });
+        responses.push(MetaResponse {
+            description: "Answer unchanged since the entity tag given in `If-None-Match`",
+            status: Some(304),
+            content: vec![],
+            headers: vec![],
+        });
+        responses.extend(ApiError::meta().responses);
+
+        MetaResponses { responses }
+    }
+
+    fn register(registry: &mut Registry) {
+        <IpGeolocation as Type>::register(registry);
+        <GeoJsonFeature as Type>::register(registry);
+        ApiError::register(registry);
+    }
+}
+
+/// Derives a weak entity tag for the lookup response of `ip_address`, made up of the dataset
+/// vintage and a digest of the address along with everything else the response depends on. New
+/// datasets have a different vintage, so reloads invalidate all previous tags.
+fn lookup_etag(
+    data_updated: SystemTime,
+    ip_address: IpAddr,
+    req: &Request,
+    format: LookupFormat,
+) -> String {
+    let vintage = data_updated
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let mut hasher = Sha256::new();
+    hasher.update(ip_address.to_string());
+    hasher.update([0]);
+    hasher.update(req.uri().to_string());
+    hasher.update([format as u8]);
+    let digest = hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    format!("W/\"{:x}-{}\"", vintage, digest)
+}
+
+/// Whether the `If-None-Match` header value lists the entity tag, using weak comparison.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque_tag = |value: &str| value.trim().trim_start_matches("W/").to_owned();
+
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .any(|item| opaque_tag(item) == opaque_tag(etag))
+}
+
+/// Whether the `Accept` header asks for `text/plain` over JSON.
+fn accepts_plain_text(req: &Request) -> bool {
+    let Some(accept) = req
+        .headers()
+        .get(poem::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let media_types = accept
+        .split(',')
+        .map(|item| item.split(';').next().unwrap_or_default().trim())
+        .collect::<Vec<_>>();
+
+    media_types.contains(&"text/plain") && !media_types.contains(&"application/json")
+}
+
+impl ApiResponse for ApiError {
+    fn meta() -> MetaResponses {
+        MetaResponses {
+            responses: vec![MetaResponse {
+                description: "",
+                status: Some(404),
+                content: vec![MetaMediaType {
+                    content_type: "application/json",
+                    schema: ApiErrorResponse::schema_ref(),
+                }],
+                headers: vec![],
+            }],
+        }
+    }
+
+    fn register(registry: &mut Registry) {
+        <ApiErrorResponse as Type>::register(registry);
+    }
+}
+
+impl From<ApiError> for poem::Error {
+    fn from(value: ApiError) -> Self {
+        let status_code = value.status_code();
+        let response: ApiErrorResponse = value.into();
+
+        Self::from_response(
+            Response::builder()
+                .status(status_code)
+                .content_type("application/json")
+                .body(response.to_json_string()),
+        )
+    }
+}
+
+impl From<ApiError> for ApiErrorResponse {
+    fn from(value: ApiError) -> Self {
+        let (_, code, message) = value.describe();
+        let mut response = Self {
+            code,
+            message: message.into_owned(),
+            ..Default::default()
+        };
+
+        if let ApiError::IpAddressNotFound { reason, nearest } = value {
+            response.outcome = reason.map(|reason| reason.to_owned());
+            response.found = nearest.as_ref().map(|_| false);
+            if let Some(nearest) = nearest {
+                response.below = nearest.below;
+                response.above = nearest.above;
+            }
+        }
+
+        response
+    }
+}
+
+/// The full IPv4 range-to-country table as newline-delimited JSON. Ranges are looked up and
+/// serialized one chunk of the address space at a time, so the table is never buffered as a whole.
+#[handler]
+pub fn dump_ipv4(dataset: Data<&Arc<ArcSwap<Dataset>>>) -> Response {
+    // Holds on to the current dataset so that a reload midway doesn't mix two datasets
+    let dataset = dataset.load_full();
+
+    // Each step carries the start of the next chunk, and the last span seen so far, which is held
+    // back in case it continues into the next chunk
+    let lines = futures_util::stream::unfold(
+        (Some(0u32), None::<Ipv4Span>),
+        move |(chunk_start, mut pending)| {
+            let dataset = dataset.clone();
+
+            async move {
+                let Some(chunk_start) = chunk_start else {
+                    let span = pending?;
+                    let mut buffer = vec![];
+                    write_span_line(&mut buffer, &span);
+                    return Some((Ok::<_, std::io::Error>(buffer), (None, None)));
+                };
+                let chunk_end = chunk_start.saturating_add(DUMP_CHUNK_SIZE - 1);
+
+                let mut buffer = vec![];
+                for span in dataset.provider.get_ipv4_spans(chunk_start, chunk_end) {
+                    match &mut pending {
+                        Some(prev)
+                            if prev.country == span.country
+                                && prev.end.checked_add(1) == Some(span.start) =>
+                        {
+                            prev.end = span.end;
+                        }
+                        _ => {
+                            if let Some(prev) = pending.replace(span) {
+                                write_span_line(&mut buffer, &prev);
+                            }
+                        }
+                    }
+                }
+
+                Some((Ok(buffer), (chunk_end.checked_add(1), pending)))
+            }
+        },
+    );
+
+    Response::builder()
+        .content_type("application/x-ndjson")
+        .body(poem::Body::from_bytes_stream(lines))
+}
+
+/// Streaming counterpart of the batch lookup endpoint for inputs of any size. Takes one IPv4
+/// address per line, and responds with one `{"ip_address": ..., "country": ..., "error_code": ...}`
+/// object per non-empty line as newline-delimited JSON. Lines are looked up as they're read, so
+/// neither the request nor the response is ever buffered as a whole.
+#[handler]
+pub fn post_ipv4_batch_stream(api: Data<&Api>, body: poem::Body) -> Response {
+    let api = api.clone();
+    let lines = BufReader::new(body.into_async_read()).lines();
+
+    let results = futures_util::stream::unfold(lines, move |mut lines| {
+        let api = api.clone();
+
+        async move {
+            let line = loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => break line,
+                    Ok(None) => return None,
+                    Err(err) => return Some((Err(err), lines)),
+                }
+            };
+
+            let mut buffer = api
+                .batch_result(line.trim().to_owned())
+                .to_json_string()
+                .into_bytes();
+            buffer.push(b'\n');
+
+            Some((Ok(buffer), lines))
+        }
+    });
+
+    Response::builder()
+        .content_type("application/x-ndjson")
+        .body(poem::Body::from_bytes_stream(results))
+}
+
+fn write_span_line(buffer: &mut Vec<u8>, span: &Ipv4Span) {
+    let line = serde_json::json!({
+        "start": Ipv4Addr::from(span.start).to_string(),
+        "end": Ipv4Addr::from(span.end).to_string(),
+        "country": span.country.as_str(),
+    });
+
+    serde_json::to_writer(&mut *buffer, &line).expect("serializing to memory never fails");
+    buffer.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::providers;
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (ApiError::InvalidIpAddress, 100, StatusCode::BAD_REQUEST),
+            (
+                ApiError::IpAddressNotFound {
+                    reason: None,
+                    nearest: None,
+                },
+                101,
+                StatusCode::NOT_FOUND,
+            ),
+            (ApiError::BatchTooLarge, 102, StatusCode::BAD_REQUEST),
+            (ApiError::InvalidCountryCode, 103, StatusCode::BAD_REQUEST),
+            (ApiError::InvalidCidr, 104, StatusCode::BAD_REQUEST),
+            (ApiError::CidrTooLarge, 105, StatusCode::BAD_REQUEST),
+            (ApiError::AsnNotLoaded, 106, StatusCode::NOT_FOUND),
+            (ApiError::ReservedAddress, 107, StatusCode::NOT_FOUND),
+            (
+                ApiError::InvalidSummaryPrefixLen,
+                108,
+                StatusCode::BAD_REQUEST,
+            ),
+            (ApiError::InvalidRange, 109, StatusCode::BAD_REQUEST),
+            (
+                ApiError::RangeTooLarge { max_size: 256 },
+                110,
+                StatusCode::BAD_REQUEST,
+            ),
+        ];
+        for (err, code, status) in cases {
+            let name = format!("{:?}", err);
+            assert_eq!(err.code(), code, "{}", name);
+            assert_eq!(err.status_code(), status, "{}", name);
+            assert_eq!(ApiErrorResponse::from(err).code, code, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_geojson_feature() {
+        let feature = GeoJsonFeature::from(IpGeolocation::new(CountryMatch {
+            country: CountryCode::new("US").unwrap(),
+            location: Some(providers::Location {
+                region: Some("California".to_owned()),
+                city: Some("Los Angeles".to_owned()),
+                latitude: 34.05,
+                longitude: -118.24,
+            }),
+            source: "ip2location",
+        }));
+        assert_eq!(
+            feature.to_json().unwrap()["geometry"],
+            json!({ "type": "Point", "coordinates": [-118.24, 34.05] })
+        );
+    }
+
+    #[test]
+    fn test_predominant_countries() {
+        let span = |start: &str, end: &str, country: &str| Ipv4Span {
+            start: u32::from(Ipv4Addr::from_str(start).unwrap()),
+            end: u32::from(Ipv4Addr::from_str(end).unwrap()),
+            country: CountryCode::new(country).unwrap(),
+        };
+        let spans = [
+            span("1.0.0.0", "1.0.0.255", "US"),
+            span("1.0.1.0", "1.0.1.127", "AU"),
+            // Crosses into the next /16
+            span("1.0.255.0", "1.1.0.255", "JP"),
+            span("1.1.1.0", "1.1.1.255", "AU"),
+            span("2.0.0.0", "2.0.0.255", "DE"),
+        ];
+        let country = |code: &str| CountryCode::new(code).unwrap();
+
+        assert_eq!(
+            predominant_countries(&spans, 16),
+            [country("JP"), country("AU"), country("DE")]
+        );
+        assert_eq!(
+            predominant_countries(&spans, 8),
+            [country("JP"), country("DE")]
+        );
+        assert_eq!(predominant_countries(&spans, 0), [country("JP")]);
+        assert!(predominant_countries(&[], 8).is_empty());
+    }
+}
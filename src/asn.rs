@@ -0,0 +1,182 @@
+use std::{
+    io::{BufRead, BufReader},
+    net::Ipv4Addr,
+    path::Path,
+};
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+use flate2::read::GzDecoder;
+
+use crate::providers::{find_range, AddressRange, GZIP_MAGIC};
+
+/// IPv4 ranges mapped to autonomous systems, loaded from the IP2Location LITE ASN database.
+#[derive(Debug)]
+pub struct AsnDatabase {
+    ranges: Vec<AsnRange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsnRecord {
+    pub asn: u32,
+    pub as_name: String,
+}
+
+#[derive(Debug)]
+struct AsnRange {
+    start: u32,
+    end: u32,
+    record: AsnRecord,
+}
+
+impl AddressRange for AsnRange {
+    type Value = u32;
+
+    fn start(&self) -> u32 {
+        self.start
+    }
+
+    fn end(&self) -> u32 {
+        self.end
+    }
+}
+
+impl AsnDatabase {
+    /// Loads the CSV-formatted `DBASNLITE` database, optionally gzip-compressed.
+    pub fn from_csv(db_path: &Path) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(std::fs::File::open(db_path)?);
+
+        let ranges = if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            parse_ranges(GzDecoder::new(reader))?
+        } else {
+            parse_ranges(reader)?
+        };
+
+        Ok(Self { ranges })
+    }
+
+    pub fn range_count(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn get_ipv4_asn(&self, ip_address: &Ipv4Addr) -> Option<AsnRecord> {
+        find_range(&self.ranges, u32::from(*ip_address))
+            .found()
+            .map(|range| range.record.clone())
+    }
+}
+
+/// Rows look like `"16777216","16777471","1.0.0.0/24","13335","CloudFlare Inc"`, with `-` in the
+/// ASN columns for unassigned ranges.
+fn parse_ranges<R>(source: R) -> anyhow::Result<Vec<AsnRange>>
+where
+    R: std::io::Read,
+{
+    let mut ranges: Vec<AsnRange> = vec![];
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(source);
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        if record.len() < 5 {
+            anyhow::bail!("invalid row");
+        }
+
+        if &record[3] == "-" {
+            continue;
+        }
+
+        let start: u32 = record[0].parse()?;
+        let end: u32 = record[1].parse()?;
+        if start > end {
+            anyhow::bail!(
+                "range starts after it ends at row {}: {}-{}",
+                record.position().map_or(0, |position| position.line()),
+                start,
+                end
+            );
+        }
+
+        if let Some(last_range) = ranges.last() {
+            if last_range.end >= start {
+                anyhow::bail!("list not sorted");
+            }
+        }
+
+        ranges.push(AsnRange {
+            start,
+            end,
+            record: AsnRecord {
+                asn: record[3].parse()?,
+                as_name: record[4].to_owned(),
+            },
+        });
+    }
+
+    ranges.shrink_to_fit();
+
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DB: &str = "\
+\"16777216\",\"16777471\",\"1.0.0.0/24\",\"13335\",\"CloudFlare Inc\"
+\"16777472\",\"16777727\",\"1.0.1.0/24\",\"-\",\"-\"
+\"16778240\",\"16779263\",\"1.0.4.0/22\",\"38803\",\"Gtelecom Pty Ltd\"
+";
+
+    fn asn(db: &AsnDatabase, ip_address: &str) -> Option<u32> {
+        db.get_ipv4_asn(&ip_address.parse().unwrap())
+            .map(|record| record.asn)
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let db = AsnDatabase {
+            ranges: parse_ranges(DB.as_bytes()).unwrap(),
+        };
+
+        // Unassigned ranges are dropped
+        assert_eq!(db.range_count(), 2);
+
+        assert_eq!(asn(&db, "1.0.0.0"), Some(13335));
+        assert_eq!(
+            db.get_ipv4_asn(&"1.0.0.255".parse().unwrap())
+                .unwrap()
+                .as_name,
+            "CloudFlare Inc"
+        );
+        assert_eq!(asn(&db, "1.0.1.1"), None);
+        assert_eq!(asn(&db, "1.0.2.1"), None);
+        assert_eq!(asn(&db, "1.0.7.255"), Some(38803));
+        assert_eq!(asn(&db, "0.255.255.255"), None);
+        assert_eq!(asn(&db, "1.0.8.0"), None);
+    }
+
+    #[test]
+    fn test_parse_ranges_invalid() {
+        let unsorted = "\
+16778240,16779263,1.0.4.0/22,38803,Gtelecom Pty Ltd
+16777216,16777471,1.0.0.0/24,13335,CloudFlare Inc
+";
+        assert_eq!(
+            parse_ranges(unsorted.as_bytes()).unwrap_err().to_string(),
+            "list not sorted"
+        );
+
+        let inverted = "\
+16777216,16777471,1.0.0.0/24,13335,CloudFlare Inc
+16779263,16778240,1.0.4.0/22,38803,Gtelecom Pty Ltd
+";
+        assert_eq!(
+            parse_ranges(inverted.as_bytes()).unwrap_err().to_string(),
+            "range starts after it ends at row 2: 16779263-16778240"
+        );
+    }
+}
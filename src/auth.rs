@@ -0,0 +1,70 @@
+use poem::{
+    http::{header, StatusCode},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Rejects requests that don't carry the shared API key in either the `Authorization: Bearer` or
+/// the `X-API-Key` header.
+#[derive(Debug, Clone)]
+pub struct ApiKeyAuth {
+    api_key: String,
+}
+
+pub struct ApiKeyAuthEndpoint<E> {
+    inner: E,
+    api_key: String,
+}
+
+impl ApiKeyAuth {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ApiKeyAuth {
+    type Output = ApiKeyAuthEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ApiKeyAuthEndpoint {
+            inner: ep,
+            api_key: self.api_key.clone(),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for ApiKeyAuthEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let bearer_key = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let header_key = req
+            .headers()
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok());
+
+        let authorized = [bearer_key, header_key]
+            .into_iter()
+            .flatten()
+            .any(|key| constant_time_eq(key.trim().as_bytes(), self.api_key.as_bytes()));
+        if !authorized {
+            return Ok(StatusCode::UNAUTHORIZED.into_response());
+        }
+
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+/// Compares without short-circuiting so that response timing doesn't leak how much of the key
+/// matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
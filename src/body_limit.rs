@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use poem::{
+    http::StatusCode,
+    web::headers::{ContentLength, HeaderMapExt},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Caps the size of request bodies sent to the given paths. Bodies declaring a larger
+/// `Content-Length` are rejected with `413 Payload Too Large` without being read, while bodies of
+/// unknown length are buffered up to the limit. Requests to other paths are let through untouched.
+#[derive(Debug, Clone)]
+pub struct BodySizeLimit {
+    max_size: usize,
+    paths: Arc<[&'static str]>,
+}
+
+pub struct BodySizeLimitEndpoint<E> {
+    inner: E,
+    limit: BodySizeLimit,
+}
+
+impl BodySizeLimit {
+    pub fn new(max_size: usize, paths: &[&'static str]) -> Self {
+        Self {
+            max_size,
+            paths: paths.into(),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for BodySizeLimit {
+    type Output = BodySizeLimitEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        BodySizeLimitEndpoint {
+            inner: ep,
+            limit: self.clone(),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for BodySizeLimitEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if !self.limit.paths.contains(&req.uri().path()) {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        if let Some(ContentLength(content_length)) = req.headers().typed_get() {
+            if content_length > self.limit.max_size as u64 {
+                return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+            }
+        }
+
+        // Chunked bodies have no declared length, so they're checked while being read. Reading
+        // past the limit fails with `413 Payload Too Large` as well.
+        let body = req
+            .take_body()
+            .into_bytes_limit(self.limit.max_size)
+            .await?;
+        req.set_body(body);
+
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Mutex, MutexGuard},
+};
+
+use lru::LruCache;
+
+use crate::providers::LookupOutcome;
+
+/// LRU cache of IPv4 lookup results, including misses.
+#[derive(Debug)]
+pub struct LookupCache {
+    inner: Mutex<CacheState>,
+}
+
+#[derive(Debug)]
+struct CacheState {
+    entries: LruCache<u32, LookupOutcome>,
+    /// Bumped on every `clear`, so that results computed against a dataset that has since been
+    /// replaced are not inserted.
+    generation: u64,
+}
+
+impl LookupCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            inner: Mutex::new(CacheState {
+                entries: LruCache::new(capacity),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Returns the cached result if present, or runs `lookup` and caches its result otherwise. The
+    /// second returned value indicates whether it was a cache hit.
+    pub fn get_or_insert_with<F>(&self, ip_value: u32, lookup: F) -> (LookupOutcome, bool)
+    where
+        F: FnOnce() -> LookupOutcome,
+    {
+        let generation = {
+            let mut state = self.lock();
+            if let Some(result) = state.entries.get(&ip_value) {
+                return (result.clone(), true);
+            }
+            state.generation
+        };
+
+        // The lock is not held while looking up so that concurrent misses don't block each other
+        let result = lookup();
+
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.entries.put(ip_value, result.clone());
+        }
+
+        (result, false)
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.generation += 1;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        // The state is always left consistent, so it's fine to keep using it after a panic
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
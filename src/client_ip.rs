@@ -0,0 +1,34 @@
+use std::net::IpAddr;
+
+use poem::Request;
+
+/// Resolves the IP address of the client that sent the request.
+///
+/// The `X-Forwarded-For` and `X-Real-IP` headers are only honored when `trust_proxy` is set, as
+/// they can be trivially spoofed by clients connecting directly.
+pub fn client_ip(req: &Request, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy {
+        if let Some(ip) = forwarded_ip(req) {
+            return Some(ip);
+        }
+    }
+
+    req.remote_addr().as_socket_addr().map(|addr| addr.ip())
+}
+
+fn forwarded_ip(req: &Request) -> Option<IpAddr> {
+    // The left-most entry is the original client
+    let forwarded_for = req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|value| value.trim().parse().ok());
+
+    forwarded_for.or_else(|| {
+        req.headers()
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    })
+}
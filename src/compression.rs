@@ -0,0 +1,117 @@
+use futures_util::{stream, StreamExt};
+use poem::{
+    error::InternalServerError,
+    http::{header, HeaderValue},
+    web::{Compress, CompressionAlgo},
+    Body, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Compresses response bodies with gzip or brotli, whichever the client prefers in its
+/// `Accept-Encoding`. Bodies shorter than `min_size` are sent as is, since compressing them
+/// saves next to nothing. Unlike `poem::middleware::Compression`, request bodies are left alone.
+#[derive(Debug, Clone)]
+pub struct ResponseCompression {
+    min_size: usize,
+}
+
+pub struct ResponseCompressionEndpoint<E> {
+    inner: E,
+    min_size: usize,
+}
+
+impl ResponseCompression {
+    pub fn new(min_size: usize) -> Self {
+        Self { min_size }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ResponseCompression {
+    type Output = ResponseCompressionEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ResponseCompressionEndpoint {
+            inner: ep,
+            min_size: self.min_size,
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for ResponseCompressionEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let algo = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(negotiate);
+
+        let mut resp = self.inner.call(req).await?.into_response();
+        resp.headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+        let algo = match algo {
+            Some(algo) if !resp.headers().contains_key(header::CONTENT_ENCODING) => algo,
+            _ => return Ok(resp),
+        };
+
+        // Streamed bodies have no known length, so chunks are read until either the threshold
+        // or the end of the body is reached
+        let mut body = resp.take_body().into_bytes_stream();
+        let mut head = Vec::new();
+        let mut head_len = 0;
+        while head_len < self.min_size {
+            match body.next().await {
+                Some(chunk) => {
+                    let chunk = chunk.map_err(InternalServerError)?;
+                    head_len += chunk.len();
+                    head.push(chunk);
+                }
+                None => {
+                    resp.set_body(head.concat());
+                    return Ok(resp);
+                }
+            }
+        }
+
+        resp.set_body(Body::from_bytes_stream(
+            stream::iter(head.into_iter().map(Ok)).chain(body),
+        ));
+        Ok(Compress::new(resp, algo).into_response())
+    }
+}
+
+/// Picks the supported coding with the highest quality value in an `Accept-Encoding` header,
+/// preferring brotli on ties.
+fn negotiate(accept_encoding: &str) -> Option<CompressionAlgo> {
+    accept_encoding
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let algo = match params.next()?.trim().to_ascii_lowercase().as_str() {
+                "br" | "*" => CompressionAlgo::BR,
+                "gzip" => CompressionAlgo::GZIP,
+                _ => return None,
+            };
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|quality| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            (quality > 0.0).then_some((algo, quality))
+        })
+        .fold(
+            None,
+            |best: Option<(CompressionAlgo, f32)>, (algo, quality)| match best {
+                Some((_, best_quality)) if best_quality > quality => best,
+                Some((best_algo, best_quality))
+                    if best_quality == quality && best_algo == CompressionAlgo::BR =>
+                {
+                    best
+                }
+                _ => Some((algo, quality)),
+            },
+        )
+        .map(|(algo, _)| algo)
+}
@@ -0,0 +1,88 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use poem::{
+    http::{header, StatusCode},
+    Body, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+use tokio::sync::Semaphore;
+
+/// Caps the number of requests to the given paths that are in flight at the same time. Requests
+/// beyond the limit wait for up to `queue_timeout` to be admitted, and are rejected with
+/// `503 Service Unavailable` and a `Retry-After` otherwise. Requests to other paths are let
+/// through untouched.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    queue_timeout: Duration,
+    paths: Arc<[&'static str]>,
+}
+
+pub struct ConcurrencyLimitEndpoint<E> {
+    inner: E,
+    limit: ConcurrencyLimit,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max_concurrent: usize, queue_timeout: Duration, paths: &[&'static str]) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            queue_timeout,
+            paths: paths.into(),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ConcurrencyLimit {
+    type Output = ConcurrencyLimitEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ConcurrencyLimitEndpoint {
+            inner: ep,
+            limit: self.clone(),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for ConcurrencyLimitEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if !self.limit.paths.contains(&req.uri().path()) {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let permit = match tokio::time::timeout(
+            self.limit.queue_timeout,
+            self.limit.semaphore.clone().acquire_owned(),
+        )
+        .await
+        {
+            Ok(Ok(permit)) => permit,
+            // The semaphore is never closed, so only timeouts end up here
+            _ => {
+                // Another request likely finishes within the time this one waited, and
+                // `Retry-After` only takes whole seconds
+                let retry_after = self.limit.queue_timeout.as_secs_f64().ceil().max(1.0) as u64;
+
+                return Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header(header::RETRY_AFTER, retry_after)
+                    .finish());
+            }
+        };
+
+        let mut resp = self.inner.call(req).await?.into_response();
+
+        // Streamed bodies (e.g. `/dump/ipv4`) are mostly produced after the handler returns, so
+        // the permit is only released once the body has been sent in full
+        let body = resp.take_body().into_bytes_stream().map(move |chunk| {
+            let _permit = &permit;
+            chunk
+        });
+        resp.set_body(Body::from_bytes_stream(body));
+
+        Ok(resp)
+    }
+}
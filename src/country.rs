@@ -0,0 +1,1598 @@
+//! ISO 3166-1 country metadata embedded into the binary.
+
+#[derive(Debug)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code in uppercase.
+    pub alpha2: &'static str,
+    /// ISO 3166-1 numeric code.
+    pub numeric: u16,
+    /// English short name.
+    pub name: &'static str,
+    pub continent: Continent,
+}
+
+/// Uppercase two-letter country code, stored inline so that lookups can hand it out without
+/// allocating.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CountryCode([u8; 2]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continent {
+    Africa,
+    Antarctica,
+    Asia,
+    Europe,
+    NorthAmerica,
+    Oceania,
+    SouthAmerica,
+}
+
+/// Looks up a country by its uppercase ISO 3166-1 alpha-2 code.
+pub fn find_country(alpha2: &str) -> Option<&'static Country> {
+    COUNTRIES
+        .binary_search_by(|country| country.alpha2.cmp(alpha2))
+        .ok()
+        .map(|ind| &COUNTRIES[ind])
+}
+
+impl CountryCode {
+    /// Parses a two-letter ASCII code in either case.
+    pub fn new(code: &str) -> Option<Self> {
+        match code.as_bytes() {
+            [a, b] if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+                Some(Self([a.to_ascii_uppercase(), b.to_ascii_uppercase()]))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever constructed from ASCII letters
+        std::str::from_utf8(&self.0).expect("country code is ASCII")
+    }
+}
+
+impl std::str::FromStr for CountryCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s).ok_or_else(|| anyhow::anyhow!("invalid country code: {}", s))
+    }
+}
+
+impl std::fmt::Display for CountryCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for CountryCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Continent {
+    /// Two-letter continent code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Africa => "AF",
+            Self::Antarctica => "AN",
+            Self::Asia => "AS",
+            Self::Europe => "EU",
+            Self::NorthAmerica => "NA",
+            Self::Oceania => "OC",
+            Self::SouthAmerica => "SA",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Africa => "Africa",
+            Self::Antarctica => "Antarctica",
+            Self::Asia => "Asia",
+            Self::Europe => "Europe",
+            Self::NorthAmerica => "North America",
+            Self::Oceania => "Oceania",
+            Self::SouthAmerica => "South America",
+        }
+    }
+}
+
+// Sorted by `alpha2` for binary search.
+static COUNTRIES: &[Country] = &[
+    Country {
+        alpha2: "AD",
+        numeric: 20,
+        name: "Andorra",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "AE",
+        numeric: 784,
+        name: "United Arab Emirates",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "AF",
+        numeric: 4,
+        name: "Afghanistan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "AG",
+        numeric: 28,
+        name: "Antigua and Barbuda",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "AI",
+        numeric: 660,
+        name: "Anguilla",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "AL",
+        numeric: 8,
+        name: "Albania",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "AM",
+        numeric: 51,
+        name: "Armenia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "AO",
+        numeric: 24,
+        name: "Angola",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "AQ",
+        numeric: 10,
+        name: "Antarctica",
+        continent: Continent::Antarctica,
+    },
+    Country {
+        alpha2: "AR",
+        numeric: 32,
+        name: "Argentina",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "AS",
+        numeric: 16,
+        name: "American Samoa",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "AT",
+        numeric: 40,
+        name: "Austria",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "AU",
+        numeric: 36,
+        name: "Australia",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "AW",
+        numeric: 533,
+        name: "Aruba",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "AX",
+        numeric: 248,
+        name: "Åland Islands",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "AZ",
+        numeric: 31,
+        name: "Azerbaijan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "BA",
+        numeric: 70,
+        name: "Bosnia and Herzegovina",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "BB",
+        numeric: 52,
+        name: "Barbados",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "BD",
+        numeric: 50,
+        name: "Bangladesh",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "BE",
+        numeric: 56,
+        name: "Belgium",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "BF",
+        numeric: 854,
+        name: "Burkina Faso",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "BG",
+        numeric: 100,
+        name: "Bulgaria",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "BH",
+        numeric: 48,
+        name: "Bahrain",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "BI",
+        numeric: 108,
+        name: "Burundi",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "BJ",
+        numeric: 204,
+        name: "Benin",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "BL",
+        numeric: 652,
+        name: "Saint Barthélemy",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "BM",
+        numeric: 60,
+        name: "Bermuda",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "BN",
+        numeric: 96,
+        name: "Brunei Darussalam",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "BO",
+        numeric: 68,
+        name: "Bolivia (Plurinational State of)",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "BQ",
+        numeric: 535,
+        name: "Bonaire, Sint Eustatius and Saba",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "BR",
+        numeric: 76,
+        name: "Brazil",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "BS",
+        numeric: 44,
+        name: "Bahamas",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "BT",
+        numeric: 64,
+        name: "Bhutan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "BV",
+        numeric: 74,
+        name: "Bouvet Island",
+        continent: Continent::Antarctica,
+    },
+    Country {
+        alpha2: "BW",
+        numeric: 72,
+        name: "Botswana",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "BY",
+        numeric: 112,
+        name: "Belarus",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "BZ",
+        numeric: 84,
+        name: "Belize",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "CA",
+        numeric: 124,
+        name: "Canada",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "CC",
+        numeric: 166,
+        name: "Cocos (Keeling) Islands",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "CD",
+        numeric: 180,
+        name: "Congo, Democratic Republic of the",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CF",
+        numeric: 140,
+        name: "Central African Republic",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CG",
+        numeric: 178,
+        name: "Congo",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CH",
+        numeric: 756,
+        name: "Switzerland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "CI",
+        numeric: 384,
+        name: "Côte d'Ivoire",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CK",
+        numeric: 184,
+        name: "Cook Islands",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "CL",
+        numeric: 152,
+        name: "Chile",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "CM",
+        numeric: 120,
+        name: "Cameroon",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CN",
+        numeric: 156,
+        name: "China",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "CO",
+        numeric: 170,
+        name: "Colombia",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "CR",
+        numeric: 188,
+        name: "Costa Rica",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "CU",
+        numeric: 192,
+        name: "Cuba",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "CV",
+        numeric: 132,
+        name: "Cabo Verde",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "CW",
+        numeric: 531,
+        name: "Curaçao",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "CX",
+        numeric: 162,
+        name: "Christmas Island",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "CY",
+        numeric: 196,
+        name: "Cyprus",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "CZ",
+        numeric: 203,
+        name: "Czechia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "DE",
+        numeric: 276,
+        name: "Germany",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "DJ",
+        numeric: 262,
+        name: "Djibouti",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "DK",
+        numeric: 208,
+        name: "Denmark",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "DM",
+        numeric: 212,
+        name: "Dominica",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "DO",
+        numeric: 214,
+        name: "Dominican Republic",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "DZ",
+        numeric: 12,
+        name: "Algeria",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "EC",
+        numeric: 218,
+        name: "Ecuador",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "EE",
+        numeric: 233,
+        name: "Estonia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "EG",
+        numeric: 818,
+        name: "Egypt",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "EH",
+        numeric: 732,
+        name: "Western Sahara",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ER",
+        numeric: 232,
+        name: "Eritrea",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ES",
+        numeric: 724,
+        name: "Spain",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "ET",
+        numeric: 231,
+        name: "Ethiopia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "FI",
+        numeric: 246,
+        name: "Finland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "FJ",
+        numeric: 242,
+        name: "Fiji",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "FK",
+        numeric: 238,
+        name: "Falkland Islands (Malvinas)",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "FM",
+        numeric: 583,
+        name: "Micronesia (Federated States of)",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "FO",
+        numeric: 234,
+        name: "Faroe Islands",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "FR",
+        numeric: 250,
+        name: "France",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "GA",
+        numeric: 266,
+        name: "Gabon",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GB",
+        numeric: 826,
+        name: "United Kingdom of Great Britain and Northern Ireland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "GD",
+        numeric: 308,
+        name: "Grenada",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "GE",
+        numeric: 268,
+        name: "Georgia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "GF",
+        numeric: 254,
+        name: "French Guiana",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "GG",
+        numeric: 831,
+        name: "Guernsey",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "GH",
+        numeric: 288,
+        name: "Ghana",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GI",
+        numeric: 292,
+        name: "Gibraltar",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "GL",
+        numeric: 304,
+        name: "Greenland",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "GM",
+        numeric: 270,
+        name: "Gambia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GN",
+        numeric: 324,
+        name: "Guinea",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GP",
+        numeric: 312,
+        name: "Guadeloupe",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "GQ",
+        numeric: 226,
+        name: "Equatorial Guinea",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GR",
+        numeric: 300,
+        name: "Greece",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "GS",
+        numeric: 239,
+        name: "South Georgia and the South Sandwich Islands",
+        continent: Continent::Antarctica,
+    },
+    Country {
+        alpha2: "GT",
+        numeric: 320,
+        name: "Guatemala",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "GU",
+        numeric: 316,
+        name: "Guam",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "GW",
+        numeric: 624,
+        name: "Guinea-Bissau",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "GY",
+        numeric: 328,
+        name: "Guyana",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "HK",
+        numeric: 344,
+        name: "Hong Kong",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "HM",
+        numeric: 334,
+        name: "Heard Island and McDonald Islands",
+        continent: Continent::Antarctica,
+    },
+    Country {
+        alpha2: "HN",
+        numeric: 340,
+        name: "Honduras",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "HR",
+        numeric: 191,
+        name: "Croatia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "HT",
+        numeric: 332,
+        name: "Haiti",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "HU",
+        numeric: 348,
+        name: "Hungary",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "ID",
+        numeric: 360,
+        name: "Indonesia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IE",
+        numeric: 372,
+        name: "Ireland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "IL",
+        numeric: 376,
+        name: "Israel",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IM",
+        numeric: 833,
+        name: "Isle of Man",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "IN",
+        numeric: 356,
+        name: "India",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IO",
+        numeric: 86,
+        name: "British Indian Ocean Territory",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IQ",
+        numeric: 368,
+        name: "Iraq",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IR",
+        numeric: 364,
+        name: "Iran (Islamic Republic of)",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "IS",
+        numeric: 352,
+        name: "Iceland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "IT",
+        numeric: 380,
+        name: "Italy",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "JE",
+        numeric: 832,
+        name: "Jersey",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "JM",
+        numeric: 388,
+        name: "Jamaica",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "JO",
+        numeric: 400,
+        name: "Jordan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "JP",
+        numeric: 392,
+        name: "Japan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KE",
+        numeric: 404,
+        name: "Kenya",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "KG",
+        numeric: 417,
+        name: "Kyrgyzstan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KH",
+        numeric: 116,
+        name: "Cambodia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KI",
+        numeric: 296,
+        name: "Kiribati",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "KM",
+        numeric: 174,
+        name: "Comoros",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "KN",
+        numeric: 659,
+        name: "Saint Kitts and Nevis",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "KP",
+        numeric: 408,
+        name: "Korea (Democratic People's Republic of)",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KR",
+        numeric: 410,
+        name: "Korea, Republic of",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KW",
+        numeric: 414,
+        name: "Kuwait",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "KY",
+        numeric: 136,
+        name: "Cayman Islands",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "KZ",
+        numeric: 398,
+        name: "Kazakhstan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "LA",
+        numeric: 418,
+        name: "Lao People's Democratic Republic",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "LB",
+        numeric: 422,
+        name: "Lebanon",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "LC",
+        numeric: 662,
+        name: "Saint Lucia",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "LI",
+        numeric: 438,
+        name: "Liechtenstein",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "LK",
+        numeric: 144,
+        name: "Sri Lanka",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "LR",
+        numeric: 430,
+        name: "Liberia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "LS",
+        numeric: 426,
+        name: "Lesotho",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "LT",
+        numeric: 440,
+        name: "Lithuania",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "LU",
+        numeric: 442,
+        name: "Luxembourg",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "LV",
+        numeric: 428,
+        name: "Latvia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "LY",
+        numeric: 434,
+        name: "Libya",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MA",
+        numeric: 504,
+        name: "Morocco",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MC",
+        numeric: 492,
+        name: "Monaco",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "MD",
+        numeric: 498,
+        name: "Moldova, Republic of",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "ME",
+        numeric: 499,
+        name: "Montenegro",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "MF",
+        numeric: 663,
+        name: "Saint Martin (French part)",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "MG",
+        numeric: 450,
+        name: "Madagascar",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MH",
+        numeric: 584,
+        name: "Marshall Islands",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "MK",
+        numeric: 807,
+        name: "North Macedonia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "ML",
+        numeric: 466,
+        name: "Mali",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MM",
+        numeric: 104,
+        name: "Myanmar",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "MN",
+        numeric: 496,
+        name: "Mongolia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "MO",
+        numeric: 446,
+        name: "Macao",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "MP",
+        numeric: 580,
+        name: "Northern Mariana Islands",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "MQ",
+        numeric: 474,
+        name: "Martinique",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "MR",
+        numeric: 478,
+        name: "Mauritania",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MS",
+        numeric: 500,
+        name: "Montserrat",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "MT",
+        numeric: 470,
+        name: "Malta",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "MU",
+        numeric: 480,
+        name: "Mauritius",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MV",
+        numeric: 462,
+        name: "Maldives",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "MW",
+        numeric: 454,
+        name: "Malawi",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "MX",
+        numeric: 484,
+        name: "Mexico",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "MY",
+        numeric: 458,
+        name: "Malaysia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "MZ",
+        numeric: 508,
+        name: "Mozambique",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "NA",
+        numeric: 516,
+        name: "Namibia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "NC",
+        numeric: 540,
+        name: "New Caledonia",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "NE",
+        numeric: 562,
+        name: "Niger",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "NF",
+        numeric: 574,
+        name: "Norfolk Island",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "NG",
+        numeric: 566,
+        name: "Nigeria",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "NI",
+        numeric: 558,
+        name: "Nicaragua",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "NL",
+        numeric: 528,
+        name: "Netherlands",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "NO",
+        numeric: 578,
+        name: "Norway",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "NP",
+        numeric: 524,
+        name: "Nepal",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "NR",
+        numeric: 520,
+        name: "Nauru",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "NU",
+        numeric: 570,
+        name: "Niue",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "NZ",
+        numeric: 554,
+        name: "New Zealand",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "OM",
+        numeric: 512,
+        name: "Oman",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "PA",
+        numeric: 591,
+        name: "Panama",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "PE",
+        numeric: 604,
+        name: "Peru",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "PF",
+        numeric: 258,
+        name: "French Polynesia",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "PG",
+        numeric: 598,
+        name: "Papua New Guinea",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "PH",
+        numeric: 608,
+        name: "Philippines",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "PK",
+        numeric: 586,
+        name: "Pakistan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "PL",
+        numeric: 616,
+        name: "Poland",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "PM",
+        numeric: 666,
+        name: "Saint Pierre and Miquelon",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "PN",
+        numeric: 612,
+        name: "Pitcairn",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "PR",
+        numeric: 630,
+        name: "Puerto Rico",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "PS",
+        numeric: 275,
+        name: "Palestine, State of",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "PT",
+        numeric: 620,
+        name: "Portugal",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "PW",
+        numeric: 585,
+        name: "Palau",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "PY",
+        numeric: 600,
+        name: "Paraguay",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "QA",
+        numeric: 634,
+        name: "Qatar",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "RE",
+        numeric: 638,
+        name: "Réunion",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "RO",
+        numeric: 642,
+        name: "Romania",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "RS",
+        numeric: 688,
+        name: "Serbia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "RU",
+        numeric: 643,
+        name: "Russian Federation",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "RW",
+        numeric: 646,
+        name: "Rwanda",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SA",
+        numeric: 682,
+        name: "Saudi Arabia",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "SB",
+        numeric: 90,
+        name: "Solomon Islands",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "SC",
+        numeric: 690,
+        name: "Seychelles",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SD",
+        numeric: 729,
+        name: "Sudan",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SE",
+        numeric: 752,
+        name: "Sweden",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "SG",
+        numeric: 702,
+        name: "Singapore",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "SH",
+        numeric: 654,
+        name: "Saint Helena, Ascension and Tristan da Cunha",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SI",
+        numeric: 705,
+        name: "Slovenia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "SJ",
+        numeric: 744,
+        name: "Svalbard and Jan Mayen",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "SK",
+        numeric: 703,
+        name: "Slovakia",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "SL",
+        numeric: 694,
+        name: "Sierra Leone",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SM",
+        numeric: 674,
+        name: "San Marino",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "SN",
+        numeric: 686,
+        name: "Senegal",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SO",
+        numeric: 706,
+        name: "Somalia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SR",
+        numeric: 740,
+        name: "Suriname",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "SS",
+        numeric: 728,
+        name: "South Sudan",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ST",
+        numeric: 678,
+        name: "Sao Tome and Principe",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "SV",
+        numeric: 222,
+        name: "El Salvador",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "SX",
+        numeric: 534,
+        name: "Sint Maarten (Dutch part)",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "SY",
+        numeric: 760,
+        name: "Syrian Arab Republic",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "SZ",
+        numeric: 748,
+        name: "Eswatini",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "TC",
+        numeric: 796,
+        name: "Turks and Caicos Islands",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "TD",
+        numeric: 148,
+        name: "Chad",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "TF",
+        numeric: 260,
+        name: "French Southern Territories",
+        continent: Continent::Antarctica,
+    },
+    Country {
+        alpha2: "TG",
+        numeric: 768,
+        name: "Togo",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "TH",
+        numeric: 764,
+        name: "Thailand",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TJ",
+        numeric: 762,
+        name: "Tajikistan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TK",
+        numeric: 772,
+        name: "Tokelau",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "TL",
+        numeric: 626,
+        name: "Timor-Leste",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TM",
+        numeric: 795,
+        name: "Turkmenistan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TN",
+        numeric: 788,
+        name: "Tunisia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "TO",
+        numeric: 776,
+        name: "Tonga",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "TR",
+        numeric: 792,
+        name: "Turkey",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TT",
+        numeric: 780,
+        name: "Trinidad and Tobago",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "TV",
+        numeric: 798,
+        name: "Tuvalu",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "TW",
+        numeric: 158,
+        name: "Taiwan, Province of China",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "TZ",
+        numeric: 834,
+        name: "Tanzania, United Republic of",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "UA",
+        numeric: 804,
+        name: "Ukraine",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "UG",
+        numeric: 800,
+        name: "Uganda",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "UM",
+        numeric: 581,
+        name: "United States Minor Outlying Islands",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "US",
+        numeric: 840,
+        name: "United States of America",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "UY",
+        numeric: 858,
+        name: "Uruguay",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "UZ",
+        numeric: 860,
+        name: "Uzbekistan",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "VA",
+        numeric: 336,
+        name: "Holy See",
+        continent: Continent::Europe,
+    },
+    Country {
+        alpha2: "VC",
+        numeric: 670,
+        name: "Saint Vincent and the Grenadines",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "VE",
+        numeric: 862,
+        name: "Venezuela (Bolivarian Republic of)",
+        continent: Continent::SouthAmerica,
+    },
+    Country {
+        alpha2: "VG",
+        numeric: 92,
+        name: "Virgin Islands (British)",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "VI",
+        numeric: 850,
+        name: "Virgin Islands (U.S.)",
+        continent: Continent::NorthAmerica,
+    },
+    Country {
+        alpha2: "VN",
+        numeric: 704,
+        name: "Viet Nam",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "VU",
+        numeric: 548,
+        name: "Vanuatu",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "WF",
+        numeric: 876,
+        name: "Wallis and Futuna",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "WS",
+        numeric: 882,
+        name: "Samoa",
+        continent: Continent::Oceania,
+    },
+    Country {
+        alpha2: "YE",
+        numeric: 887,
+        name: "Yemen",
+        continent: Continent::Asia,
+    },
+    Country {
+        alpha2: "YT",
+        numeric: 175,
+        name: "Mayotte",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ZA",
+        numeric: 710,
+        name: "South Africa",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ZM",
+        numeric: 894,
+        name: "Zambia",
+        continent: Continent::Africa,
+    },
+    Country {
+        alpha2: "ZW",
+        numeric: 716,
+        name: "Zimbabwe",
+        continent: Continent::Africa,
+    },
+];
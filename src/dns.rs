@@ -0,0 +1,189 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+use arc_swap::ArcSwap;
+use hickory_proto::{
+    op::{Edns, Message, MessageType, Metadata, OpCode, ResponseCode},
+    rr::{rdata::TXT, Name, RData, Record, RecordType},
+};
+use log::{debug, warn};
+use tokio::net::UdpSocket;
+
+use crate::{providers::IpgeoProvider, util, Dataset};
+
+/// TTL of TXT answers. Datasets only change on reloads, so answers can be cached for a while.
+const TXT_TTL: u32 = 300;
+
+/// Largest UDP message accepted, as advertised by EDNS clients.
+const MAX_MESSAGE_LEN: usize = 4096;
+
+/// Answers TXT queries under `zone` with the country code of the address encoded in the query
+/// name, with labels in reverse order like in reverse DNS. For example, `4.3.2.1.geo.example.`
+/// asks for the country of `1.2.3.4`. IPv6 addresses are given as 32 reversed nibbles like in
+/// `ip6.arpa`.
+pub async fn serve(socket: UdpSocket, zone: Name, dataset: Arc<ArcSwap<Dataset>>) {
+    let mut buffer = vec![0; MAX_MESSAGE_LEN];
+
+    loop {
+        // Errors are per datagram (e.g. ICMP errors reported by some platforms), so they don't
+        // stop the server
+        let (len, peer) = match socket.recv_from(&mut buffer).await {
+            Ok(value) => value,
+            Err(err) => {
+                debug!("Unable to receive DNS message: {}", err);
+                continue;
+            }
+        };
+
+        // Undecodable messages are dropped, as there's no reliable ID to respond to
+        let request = match Message::from_vec(&buffer[..len]) {
+            Ok(request) => request,
+            Err(err) => {
+                debug!("Dropping malformed DNS message from {}: {}", peer, err);
+                continue;
+            }
+        };
+        if request.metadata.message_type != MessageType::Query {
+            continue;
+        }
+
+        let response = respond(&request, &zone, &dataset.load().provider);
+        match response.to_vec() {
+            Ok(response) => {
+                if let Err(err) = socket.send_to(&response, peer).await {
+                    debug!("Unable to send DNS response to {}: {}", peer, err);
+                }
+            }
+            Err(err) => warn!("Unable to encode DNS response: {}", err),
+        }
+    }
+}
+
+fn respond(request: &Message, zone: &Name, provider: &IpgeoProvider) -> Message {
+    let mut response = Message::new(0, MessageType::Response, OpCode::Query);
+    response.metadata = Metadata::response_from_request(&request.metadata);
+    response.queries = request.queries.clone();
+    if request.edns.is_some() {
+        response.edns = Some(Edns::new());
+    }
+
+    let query = match request.queries.as_slice() {
+        [query] if request.metadata.op_code == OpCode::Query => query,
+        [_] => {
+            response.metadata.response_code = ResponseCode::NotImp;
+            return response;
+        }
+        _ => {
+            response.metadata.response_code = ResponseCode::FormErr;
+            return response;
+        }
+    };
+
+    let name = query.name().to_lowercase();
+    if !zone.zone_of(&name) {
+        response.metadata.response_code = ResponseCode::Refused;
+        return response;
+    }
+    response.metadata.authoritative = true;
+
+    let labels = name
+        .iter()
+        .take(usize::from(name.num_labels() - zone.num_labels()))
+        .collect::<Vec<_>>();
+    let country_match = match parse_reversed_address(&labels) {
+        Some(IpAddr::V4(ip_address)) if util::is_reserved_ipv4(&ip_address) => None,
+        Some(IpAddr::V4(ip_address)) => provider.match_ipv4(&ip_address).found(),
+        Some(IpAddr::V6(ip_address)) if util::is_reserved_ipv6(&ip_address) => None,
+        Some(IpAddr::V6(ip_address)) => provider.match_ipv6(&ip_address).found(),
+        None => None,
+    };
+    let Some(country_match) = country_match else {
+        response.metadata.response_code = ResponseCode::NXDomain;
+        return response;
+    };
+
+    // Other record types get an empty answer, as the name itself exists
+    if matches!(query.query_type(), RecordType::TXT | RecordType::ANY) {
+        response.answers.push(Record::from_rdata(
+            query.name().clone(),
+            TXT_TTL,
+            RData::TXT(TXT::new(vec![country_match.country.to_string()])),
+        ));
+    }
+
+    response
+}
+
+/// Parses the labels in front of the zone as either 4 reversed IPv4 octets or 32 reversed IPv6
+/// nibbles.
+fn parse_reversed_address(labels: &[&[u8]]) -> Option<IpAddr> {
+    let labels = labels
+        .iter()
+        .rev()
+        .map(|label| std::str::from_utf8(label).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match labels.len() {
+        4 => labels.join(".").parse::<Ipv4Addr>().ok().map(IpAddr::V4),
+        32 => {
+            let mut value = 0u128;
+            for label in labels {
+                if label.len() != 1 {
+                    return None;
+                }
+                value = (value << 4) | u128::from_str_radix(label, 16).ok()?;
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(value)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Option<IpAddr> {
+        let labels = name.split('.').map(str::as_bytes).collect::<Vec<_>>();
+        parse_reversed_address(&labels)
+    }
+
+    #[test]
+    fn test_parse_reversed_address() {
+        let cases = [
+            ("4.3.2.1", Some("1.2.3.4")),
+            ("255.0.0.10", Some("10.0.0.255")),
+            (
+                "8.8.8.8.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.1.0.0.2",
+                Some("2001:4860::8888"),
+            ),
+            (
+                "F.F.F.F.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.1.0.0.2",
+                Some("2001:4860::ffff"),
+            ),
+            ("3.2.1", None),
+            ("5.4.3.2.1", None),
+            ("4.3.2.256", None),
+            ("4.3.2.01", None),
+            ("a.b.c.d", None),
+            (
+                "88.8.8.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.1.0.0.2.0",
+                None,
+            ),
+            (
+                "g.8.8.8.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.1.0.0.2",
+                None,
+            ),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                parse(name),
+                expected.map(|value| value.parse().unwrap()),
+                "{}",
+                name
+            );
+        }
+    }
+}
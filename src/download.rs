@@ -0,0 +1,141 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use log::info;
+use sha2::{Digest, Sha256};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Database given either as a local path or as an `http(s)://` URL to download on every load.
+#[derive(Debug, Clone)]
+pub enum DatabaseLocation {
+    Path(PathBuf),
+    Url(String),
+}
+
+/// Local copy of a database, removed once dropped if it was downloaded.
+#[derive(Debug)]
+pub enum LocalFile<'a> {
+    Existing(&'a Path),
+    Downloaded(PathBuf),
+}
+
+impl DatabaseLocation {
+    /// Local path of the database, or `None` for URLs.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Url(_) => None,
+        }
+    }
+
+    /// Downloads the database into a temporary file if it's a URL, verifying it against the
+    /// hex-encoded SHA-256 checksum if given. The checksum is not checked for local paths.
+    pub fn fetch(&self, sha256: Option<&str>) -> anyhow::Result<LocalFile<'_>> {
+        match self {
+            Self::Path(path) => Ok(LocalFile::Existing(path)),
+            Self::Url(url) => download(url, sha256),
+        }
+    }
+}
+
+impl FromStr for DatabaseLocation {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if s.starts_with("http://") || s.starts_with("https://") {
+            Self::Url(s.to_owned())
+        } else {
+            Self::Path(s.into())
+        })
+    }
+}
+
+impl LocalFile<'_> {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Existing(path) => path,
+            Self::Downloaded(path) => path,
+        }
+    }
+}
+
+impl Drop for LocalFile<'_> {
+    fn drop(&mut self) {
+        if let Self::Downloaded(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn download(url: &str, sha256: Option<&str>) -> anyhow::Result<LocalFile<'static>> {
+    info!("Downloading {}", url);
+
+    // The default timeout covers reading the whole body, which large databases can exceed
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(None)
+        .build()?;
+    let mut response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| anyhow::anyhow!("unable to download {}: {}", url, err))?;
+
+    let path = std::env::temp_dir().join(format!("ipgeod-{}", uuid::Uuid::new_v4()));
+    let output = create_private(&path)
+        .map_err(|err| anyhow::anyhow!("unable to create {}: {}", path.display(), err))?;
+    // Wrapped right after creating so that partial downloads are removed on errors too
+    let file = LocalFile::Downloaded(path);
+    let mut writer = BufWriter::new(output);
+    let mut hasher = Sha256::new();
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = response
+            .read(&mut buffer)
+            .map_err(|err| anyhow::anyhow!("unable to download {}: {}", url, err))?;
+        if len == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..len]);
+        writer.write_all(&buffer[..len])?;
+    }
+    writer.flush()?;
+
+    if let Some(expected) = sha256 {
+        let actual = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        if actual != expected {
+            anyhow::bail!(
+                "checksum mismatch for {}: expected SHA-256 {}, got {}",
+                url,
+                expected,
+                actual
+            );
+        }
+    }
+
+    Ok(file)
+}
+
+/// Creates a file only readable by the current user, failing if anything already exists at the
+/// path. Temporary directories are commonly shared, so this keeps other users from planting a
+/// symlink there to redirect the write, or from reading the download.
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)
+}
@@ -0,0 +1,45 @@
+//! IP geolocation lookups against local databases, as served over HTTP by the `ipgeod` binary.
+//!
+//! The lookup engine can be embedded into other applications without the server. Load one of the
+//! supported databases into an [`IpgeoProvider`] and query it directly:
+//!
+//! ```
+//! use ipgeod::{
+//!     providers::{CountryFilter, HerrbischoffProvider},
+//!     IpgeoProvider,
+//! };
+//!
+//! let provider = IpgeoProvider::Herrbischoff(HerrbischoffProvider::from_bytes(
+//!     &[("us", b"1.2.3.0/24\n".as_slice())],
+//!     &[],
+//!     &CountryFilter::default(),
+//! )?);
+//!
+//! let country = provider.get_ipv4_country(&"1.2.3.4".parse()?);
+//! assert_eq!(country.found().map(|item| item.to_string()), Some("US".to_owned()));
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The HTTP endpoints themselves live in [`api`], for mounting into other `poem` applications.
+
+pub mod api;
+
+pub mod asn;
+
+pub mod cache;
+
+pub mod client_ip;
+
+pub mod country;
+pub use country::CountryCode;
+
+pub mod metrics;
+
+pub mod providers;
+pub use providers::IpgeoProvider;
+
+pub mod request_id;
+
+pub mod telemetry;
+
+pub mod util;
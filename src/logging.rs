@@ -0,0 +1,48 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use log::kv::{Error, Key, Value, VisitSource};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines from `env_logger`
+    Pretty,
+    /// One JSON object per line, including structured fields attached to the record
+    Json,
+}
+
+/// Collects structured key-value pairs of a record into a JSON object.
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let mut object = serde_json::Map::new();
+            object.insert(
+                "timestamp".into(),
+                buf.timestamp_millis().to_string().into(),
+            );
+            object.insert("level".into(), record.level().as_str().into());
+            object.insert("target".into(), record.target().into());
+            object.insert("message".into(), record.args().to_string().into());
+
+            // Fields are best-effort; a value that fails to serialize is simply left out
+            let _ = record.key_values().visit(&mut JsonFields(&mut object));
+
+            writeln!(buf, "{}", serde_json::Value::Object(object))
+        });
+    }
+
+    builder.init();
+}
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = serde_json::to_value(value).map_err(Error::boxed)?;
+        self.0.insert(key.to_string(), value);
+
+        Ok(())
+    }
+}
@@ -1,173 +1,1458 @@
-use std::{net::Ipv4Addr, path::PathBuf, str::FromStr};
+use std::{
+    io::BufRead,
+    net::IpAddr,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
-use clap::Parser;
-use log::info;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log::{error, info};
+use notify::{RecursiveMode, Watcher};
+#[cfg(unix)]
+use poem::listener::UnixListener;
 use poem::{
-    http::StatusCode,
-    listener::TcpListener,
-    middleware::{Cors, Tracing},
+    endpoint::BoxEndpoint,
+    get, handler,
+    http::{header::HeaderName, HeaderValue, Method, StatusCode, Uri},
+    listener::{Acceptor, AcceptorExt, Listener, RustlsCertificate, RustlsConfig, TcpListener},
+    middleware::{Cors, OpenTelemetryTracing, Tracing},
+    post,
+    web::Data,
     EndpointExt, Response, Route,
 };
-use poem_openapi::{
-    param::Path,
-    payload::Json,
-    registry::{MetaMediaType, MetaResponse, MetaResponses, Registry},
-    types::{ToJSON, Type},
-    ApiResponse, Object, OpenApi, OpenApiService,
-};
+use poem_openapi::{types::ToJSON, Object, OpenApiService};
+
+mod auth;
+use auth::ApiKeyAuth;
+
+mod body_limit;
+use body_limit::BodySizeLimit;
+
+mod compression;
+use compression::ResponseCompression;
+
+mod concurrency_limit;
+use concurrency_limit::ConcurrencyLimit;
+
+mod dns;
 
-mod providers;
-use providers::IpgeoProvider;
+mod download;
+use download::DatabaseLocation;
 
-use crate::providers::{HerrbischoffProvider, Ip2locationProvider};
+mod logging;
+use logging::LogFormat;
+
+mod rate_limit;
+use rate_limit::RateLimit;
+
+mod request_timeout;
+use request_timeout::RequestTimeout;
+
+use ipgeod::{
+    api::{dump_ipv4, post_ipv4_batch_stream, Api, Dataset, DatasetFile, NotFoundStatus},
+    asn::AsnDatabase,
+    cache::LookupCache,
+    country::CountryCode,
+    metrics::Metrics,
+    providers::{
+        self, CountryFilter, CountryMatch, HerrbischoffProvider, Ip2locationOptions,
+        Ip2locationProvider, IpgeoProvider, LineFormat, MaxmindProvider, RirProvider,
+    },
+    request_id::RequestId,
+    telemetry, util,
+};
 
 #[derive(Debug, Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(
+        long,
+        env,
+        global = true,
+        value_enum,
+        default_value = "pretty",
+        help = "Format of log output"
+    )]
+    log_format: LogFormat,
+    #[clap(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve the HTTP API (default)
+    Serve(Box<ServeArgs>),
+    /// Load the database and report what was loaded without serving
+    Check(Box<SourceArgs>),
+    /// Load the database and print the country code of a single address
+    Lookup(Box<LookupArgs>),
+    /// Load the database and print the country codes of addresses listed one per line, as CSV
+    LookupFile(Box<LookupFileArgs>),
+}
+
+#[derive(Debug, Args)]
+struct LookupArgs {
+    #[clap(help = "IPv4 or IPv6 address to look up")]
+    ip_address: IpAddr,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Debug, Args)]
+struct LookupFileArgs {
+    #[clap(help = "File with one IPv4 or IPv6 address per line, or - to read from stdin")]
+    path: PathBuf,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    #[clap(
+        long,
+        env,
+        default_value = "0.0.0.0",
+        help = "Address to bind to, e.g. 127.0.0.1 or :: for dual-stack"
+    )]
+    host: IpAddr,
     #[clap(long, env, default_value = "3000", help = "Port to listen on")]
     port: u16,
-    #[clap(long, env, help = "Path to the country-ip-blocks repository")]
-    herrbischoff_path: Option<PathBuf>,
+    #[cfg(unix)]
+    #[clap(
+        long,
+        env,
+        help = "Path of a Unix domain socket to listen on instead of TCP, in which case --host and \
+                --port are ignored"
+    )]
+    unix_socket: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        requires = "dns_zone",
+        help = "UDP port to answer DNS TXT queries for countries on (disabled if unset)"
+    )]
+    dns_port: Option<u16>,
+    #[clap(
+        long,
+        env,
+        value_parser = parse_dns_zone,
+        help = "Zone to answer DNS queries under, e.g. geo.example for 4.3.2.1.geo.example"
+    )]
+    dns_zone: Option<hickory_proto::rr::Name>,
+    #[clap(
+        long,
+        env,
+        help = "Number of runtime worker threads (defaults to the number of CPUs)"
+    )]
+    worker_threads: Option<NonZeroUsize>,
+    #[clap(
+        long,
+        env,
+        help = "Trust X-Forwarded-For and X-Real-IP headers for resolving client addresses"
+    )]
+    trust_proxy: bool,
+    #[clap(
+        long,
+        env,
+        default_value = "0",
+        help = "Number of IPv4 lookup results to keep in an LRU cache (0 disables caching)"
+    )]
+    cache_size: usize,
+    #[clap(
+        long,
+        env,
+        help = "Maximum requests per second allowed from each client IP (unlimited if unset)"
+    )]
+    rate_limit: Option<NonZeroU32>,
+    #[clap(
+        long,
+        env,
+        help = "Maximum number of batch and dump requests served at the same time (unlimited if \
+                unset)"
+    )]
+    max_concurrent: Option<NonZeroUsize>,
+    #[clap(
+        long,
+        env,
+        default_value = "4194304",
+        help = "Maximum size in bytes of batch request bodies, beyond which requests are rejected \
+                with 413 Payload Too Large"
+    )]
+    max_body_size: usize,
+    #[clap(
+        long,
+        env,
+        default_value = "16777216",
+        help = "Maximum number of addresses in an /ipv4/range/{start}/{end} query"
+    )]
+    max_range_size: u64,
+    #[clap(
+        long,
+        env,
+        help = "Shared secret required in the Authorization (Bearer) or X-API-Key header on \
+                every request except /healthz"
+    )]
+    api_key: Option<String>,
+    #[clap(
+        long,
+        env,
+        default_value = "10",
+        help = "Seconds to wait for in-flight requests on shutdown before closing connections"
+    )]
+    shutdown_timeout: u64,
+    #[clap(
+        long,
+        env,
+        help = "Seconds a connection may stay idle before it's closed (kept open indefinitely if \
+                unset)"
+    )]
+    keep_alive_timeout: Option<u64>,
+    #[clap(
+        long,
+        env,
+        help = "Seconds API requests may take to be answered before failing with 504 Gateway \
+                Timeout, after which streamed responses are cut off too (unlimited if unset)"
+    )]
+    request_timeout: Option<u64>,
+    #[clap(
+        long,
+        env,
+        help = "OTLP/gRPC endpoint to export a trace span per API request to, e.g. \
+                http://localhost:4317 (no traces are exported if unset)"
+    )]
+    otlp_endpoint: Option<String>,
+    #[clap(
+        long,
+        env,
+        requires = "tls_key",
+        help = "Path to the PEM-encoded TLS certificate chain for serving HTTPS"
+    )]
+    tls_cert: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        requires = "tls_cert",
+        help = "Path to the PEM-encoded TLS private key for serving HTTPS"
+    )]
+    tls_key: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        help = "Path to the IP2Location LITE CSV-formatted ASN database"
+    )]
+    ip2location_asn: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        conflicts_with = "mmap",
+        help = "Reload the database automatically when its files change on disk"
+    )]
+    watch: bool,
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "404",
+        help = "HTTP status for single-address lookups that find nothing: 404 with an error body, \
+                204 with no body, or 200 with a null country"
+    )]
+    not_found_status: NotFoundStatus,
+    #[clap(
+        long,
+        env,
+        conflicts_with = "not_found_status",
+        help = "Country code to answer single-address lookups that find nothing with, e.g. XX, \
+                flagged with is_default in the response"
+    )]
+    default_country: Option<CountryCode>,
+    #[clap(
+        long,
+        env,
+        help = "Duplicate the country field as country_code in lookup responses, for clients \
+                expecting that name"
+    )]
+    country_code_alias: bool,
+    #[clap(
+        long,
+        env,
+        help = "Serve the full IPv4 range-to-country table at /dump/ipv4 as newline-delimited JSON"
+    )]
+    enable_dump: bool,
+    #[clap(
+        long,
+        env,
+        default_value_t = true,
+        action = clap::ArgAction::Set,
+        help = "Compress API responses with gzip or brotli when the client accepts it"
+    )]
+    compression: bool,
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        value_parser = parse_cors_origin,
+        help = "Origin allowed to make cross-origin requests, e.g. https://example.com \
+                (repeatable; any origin if unset)"
+    )]
+    cors_allow_origin: Vec<HeaderValue>,
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        value_parser = Method::from_str,
+        help = "HTTP method allowed in cross-origin requests (repeatable; any method if unset)"
+    )]
+    cors_allow_method: Vec<Method>,
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        value_parser = HeaderName::from_str,
+        help = "Request header allowed in cross-origin requests (repeatable; any header if unset)"
+    )]
+    cors_allow_header: Vec<HeaderName>,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+struct SourceArgs {
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Path to the country-ip-blocks repository. Can be repeated to layer overlays on \
+                top, with later paths taking precedence"
+    )]
+    herrbischoff_path: Vec<PathBuf>,
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "cidr",
+        help = "Layout of lines in herrbischoff .cidr files"
+    )]
+    herrbischoff_format: HerrbischoffFormat,
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "sorted",
+        help = "Structure to look up IPv4 addresses in herrbischoff blocks with"
+    )]
+    index: IndexKind,
+    #[clap(
+        long,
+        env,
+        help = "Path or http(s):// URL to the IP2Location LITE CSV-formatted IPv4 database. URLs \
+                are downloaded on every load"
+    )]
+    ip2location_db: Option<DatabaseLocation>,
+    #[clap(
+        long,
+        env,
+        value_parser = parse_sha256,
+        help = "Hex-encoded SHA-256 checksum to verify the downloaded --ip2location-db against"
+    )]
+    ip2location_db_sha256: Option<String>,
+    #[clap(
+        long,
+        env,
+        help = "Path to the IP2Location LITE CSV-formatted IPv6 database"
+    )]
+    ip2location_ipv6_db: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        conflicts_with_all = ["ip2location_db", "ip2location_ipv6_db"],
+        help = "Path to the IP2Location LITE BIN-formatted database"
+    )]
+    ip2location_bin: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        conflicts_with = "ip2location_bin",
+        help = "Path to a binary index of the parsed IP2Location CSV databases, used instead of \
+                the databases on restart as long as they are unchanged"
+    )]
+    index_cache: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        help = "Path to the MaxMind DB file with country data, e.g. GeoLite2-Country.mmdb"
+    )]
+    maxmind_db: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        help = "Path to a directory of RIR delegated statistics files, e.g. \
+                delegated-ripencc-extended-latest"
+    )]
+    rir_path: Option<PathBuf>,
+    #[clap(
+        long,
+        env,
+        help = "Memory-map IP2Location CSV databases while loading to reduce startup memory. \
+                Database files must then only be replaced atomically (e.g. by renaming a new \
+                file over them), never rewritten in place"
+    )]
+    mmap: bool,
+    #[clap(
+        long,
+        env,
+        help = "Sort IP2Location CSV rows after loading instead of requiring sorted input"
+    )]
+    sort_on_load: bool,
+    #[clap(
+        long,
+        env,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "herrbischoff,ip2location,maxmind,rir",
+        help = "Order in which configured providers are tried when more than one is provided"
+    )]
+    provider_order: Vec<ProviderKind>,
+    #[clap(
+        long,
+        env,
+        value_enum,
+        default_value = "first",
+        help = "How to resolve addresses that configured providers disagree on the country of"
+    )]
+    conflict_policy: ConflictPolicy,
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        value_parser = CountryCode::from_str,
+        help = "Only load ranges of these two-letter country codes, e.g. DE,FR,IT"
+    )]
+    countries: Vec<CountryCode>,
     #[clap(
         long,
         env,
-        help = "Path to the IP2Location LITE CSV-formatted database"
+        value_delimiter = ',',
+        value_parser = CountryCode::from_str,
+        help = "Skip ranges of these two-letter country codes when loading"
     )]
-    ip2location_db: Option<PathBuf>,
+    exclude_countries: Vec<CountryCode>,
+    #[clap(
+        long,
+        env,
+        value_parser = CountryCode::from_str,
+        help = "Keep IP2Location ranges without a country under this placeholder code, e.g. ZZ, \
+                instead of dropping them"
+    )]
+    unknown_country: Option<CountryCode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ProviderKind {
+    Herrbischoff,
+    Ip2location,
+    Maxmind,
+    Rir,
+}
+
+impl ProviderKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Herrbischoff => "herrbischoff",
+            Self::Ip2location => "ip2location",
+            Self::Maxmind => "maxmind",
+            Self::Rir => "rir",
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum HerrbischoffFormat {
+    /// One CIDR block per line, with the country taken from the <cc>.cidr file name
+    Cidr,
+    /// Also accept `CIDR,CC` lines carrying their own country, e.g. in aggregated all.cidr files
+    Mixed,
+}
+
+impl From<HerrbischoffFormat> for LineFormat {
+    fn from(value: HerrbischoffFormat) -> Self {
+        match value {
+            HerrbischoffFormat::Cidr => Self::Cidr,
+            HerrbischoffFormat::Mixed => Self::Mixed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum IndexKind {
+    /// Binary search over the sorted blocks
+    Sorted,
+    /// Longest-prefix-match trie, which takes more memory but is faster with many small blocks
+    Lpm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConflictPolicy {
+    /// The first provider in `--provider-order` with a match wins
+    First,
+    /// The herrbischoff match wins if there's one, falling back to the first match
+    PreferHerrbischoff,
+    /// The ip2location match wins if there's one, falling back to the first match
+    PreferIp2location,
+    /// The maxmind match wins if there's one, falling back to the first match
+    PreferMaxmind,
+    /// The rir match wins if there's one, falling back to the first match
+    PreferRir,
+    /// Addresses are reported as not found when matching providers disagree
+    DisagreeNull,
+}
+
+impl From<ConflictPolicy> for providers::ConflictPolicy {
+    fn from(value: ConflictPolicy) -> Self {
+        match value {
+            ConflictPolicy::First => Self::First,
+            ConflictPolicy::PreferHerrbischoff => Self::Prefer(ProviderKind::Herrbischoff.name()),
+            ConflictPolicy::PreferIp2location => Self::Prefer(ProviderKind::Ip2location.name()),
+            ConflictPolicy::PreferMaxmind => Self::Prefer(ProviderKind::Maxmind.name()),
+            ConflictPolicy::PreferRir => Self::Prefer(ProviderKind::Rir.name()),
+            ConflictPolicy::DisagreeNull => Self::DisagreeNull,
+        }
+    }
+}
+
+/// Providers loaded from the configured sources.
 #[derive(Debug)]
-struct Api {
+struct LoadedSources {
     provider: IpgeoProvider,
+    /// Configured providers that failed to load and are left out of `provider`.
+    failed: Vec<FailedProvider>,
+}
+
+/// Progress and outcome of in-process reloads, reported on `/healthz`.
+#[derive(Debug, Default)]
+struct ReloadState {
+    /// Number of reloads currently loading a new dataset. SIGHUP and `--watch` can trigger reloads
+    /// independently, so they may overlap.
+    in_progress: AtomicUsize,
+    /// Set when the most recent reload failed, and cleared once one succeeds.
+    last_error: ArcSwapOption<ReloadError>,
 }
 
 #[derive(Debug)]
-pub enum ApiError {
-    InvalidIpAddress,
-    IpAddressNotFound,
+struct ReloadError {
+    failed_at: SystemTime,
+    error: String,
+}
+
+#[derive(Debug)]
+struct FailedProvider {
+    kind: ProviderKind,
+    err: anyhow::Error,
+}
+
+impl LoadedSources {
+    /// Fails if any of the configured providers failed to load.
+    fn require_all(mut self) -> anyhow::Result<IpgeoProvider> {
+        match self.failed.pop() {
+            Some(failed) => Err(failed
+                .err
+                .context(format!("failed to load {} provider", failed.kind.name()))),
+            None => Ok(self.provider),
+        }
+    }
+}
+
+/// How long requests over `--max-concurrent` wait to be admitted before getting rejected.
+const CONCURRENCY_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Responses shorter than this, such as single address lookups, aren't worth compressing.
+const COMPRESSION_MIN_SIZE: usize = 1024;
+/// How long database files must stay unchanged before a watched change triggers a reload.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Object)]
+struct HealthStatus {
+    /// Active provider variant
+    provider: String,
+    /// Providers lookups are served from, in the order they're tried
+    active_providers: Vec<String>,
+    /// Configured providers that failed to load and are not being served from
+    failed_providers: Vec<ProviderError>,
+    /// Number of loaded IPv4 ranges or CIDR blocks
+    ipv4_ranges: u64,
+    /// Number of loaded IPv6 ranges or CIDR blocks
+    ipv6_ranges: u64,
+    /// Whether a reload is loading a new dataset at the moment. Lookups keep being served from
+    /// the current dataset in the meantime.
+    reload_in_progress: bool,
+    /// Why the most recent reload failed, if it did. Lookups keep being served from the last
+    /// dataset that loaded successfully until a reload succeeds.
+    last_reload_error: Option<String>,
+    /// When the most recent reload failed as a Unix timestamp in seconds, if it did
+    last_reload_failed_at: Option<u64>,
+}
+
+#[derive(Debug, Object)]
+struct VersionInfo {
+    /// Version of the `ipgeod` build
+    version: String,
+    /// Git commit the binary was built from, if built from a git checkout
+    git_commit: Option<String>,
+    /// Active provider variant
+    provider: String,
+    /// Providers lookups are served from, in the order they're tried
+    active_providers: Vec<String>,
+    /// Database files or directories the active dataset was loaded from
+    datasets: Vec<DatasetInfo>,
+}
+
+#[derive(Debug, Object)]
+struct DatasetInfo {
+    /// Path of the database file or directory
+    path: String,
+    /// Last modification time as a Unix timestamp in seconds. For directories, this is the latest
+    /// modification time of any file within.
+    modified_at: Option<u64>,
 }
 
 #[derive(Debug, Object)]
-struct ApiErrorResponse {
-    code: u32,
-    message: String,
+struct ProviderError {
+    /// Name of the provider
+    provider: String,
+    /// Why the provider failed to load
+    error: String,
+}
+
+/// Readiness probe reporting the loaded dataset. Served outside the OpenAPI service so that it's
+/// not traced.
+#[handler]
+fn healthz(
+    dataset: Data<&Arc<ArcSwap<Dataset>>>,
+    failed_providers: Data<&Arc<ArcSwap<Vec<FailedProvider>>>>,
+    reload_state: Data<&Arc<ReloadState>>,
+) -> Response {
+    let dataset = dataset.load();
+    let last_reload_error = reload_state.last_error.load();
+    let provider = &dataset.provider;
+    let status = HealthStatus {
+        provider: provider.name().to_owned(),
+        active_providers: provider
+            .provider_names()
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+        failed_providers: failed_providers
+            .load()
+            .iter()
+            .map(|item| ProviderError {
+                provider: item.kind.name().to_owned(),
+                error: format!("{:#}", item.err),
+            })
+            .collect(),
+        ipv4_ranges: provider.ipv4_range_count() as u64,
+        ipv6_ranges: provider.ipv6_range_count() as u64,
+        reload_in_progress: reload_state.in_progress.load(Ordering::Relaxed) > 0,
+        last_reload_error: last_reload_error.as_ref().map(|item| item.error.clone()),
+        last_reload_failed_at: last_reload_error.as_ref().and_then(|item| {
+            item.failed_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_secs())
+        }),
+    };
+
+    Response::builder()
+        .content_type("application/json")
+        .body(status.to_json_string())
 }
 
-#[derive(Debug, Clone, Object)]
-struct IpGeolocation {
-    country: String,
+/// Reports the build and the dataset being served. Served outside the OpenAPI service along with
+/// `/healthz`.
+#[handler]
+fn version_info(dataset: Data<&Arc<ArcSwap<Dataset>>>) -> Response {
+    let dataset = dataset.load();
+    let provider = &dataset.provider;
+    let info = VersionInfo {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_commit: option_env!("IPGEOD_GIT_COMMIT").map(str::to_owned),
+        provider: provider.name().to_owned(),
+        active_providers: provider
+            .provider_names()
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+        datasets: dataset
+            .files
+            .iter()
+            .map(|item| DatasetInfo {
+                path: item.path.display().to_string(),
+                modified_at: item
+                    .modified
+                    .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs()),
+            })
+            .collect(),
+    };
+
+    Response::builder()
+        .content_type("application/json")
+        .body(info.to_json_string())
 }
 
-impl Api {
-    fn new(provider: IpgeoProvider) -> Self {
-        Self { provider }
+/// Prometheus metrics in the text exposition format.
+#[handler]
+fn prometheus_metrics(metrics: Data<&Arc<Metrics>>) -> Response {
+    match metrics.render() {
+        Ok(body) => Response::builder()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        Err(err) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(err.to_string()),
     }
 }
 
-#[OpenApi]
-impl Api {
-    #[oai(path = "/ipv4/:ip_address", method = "get")]
-    /// Gets the two-letter ISO 3166 country code associated with the IPv4 address
-    async fn get_ipv4(&self, ip_address: Path<String>) -> Result<Json<IpGeolocation>, ApiError> {
-        let ip_address =
-            Ipv4Addr::from_str(&ip_address.0).map_err(|_| ApiError::InvalidIpAddress)?;
+impl SourceArgs {
+    /// Loads every configured provider independently. Providers that fail to load are logged and
+    /// left out, unless none of them loads at all.
+    fn load(&self) -> anyhow::Result<LoadedSources> {
+        let mut providers = vec![];
+        let mut failed = vec![];
+        let countries = CountryFilter {
+            include: self.countries.clone(),
+            exclude: self.exclude_countries.clone(),
+        };
 
-        match self.provider.get_ipv4_country(&ip_address) {
-            Some(country) => Ok(Json(IpGeolocation { country })),
-            None => Err(ApiError::IpAddressNotFound),
+        for kind in ProviderKind::value_variants() {
+            if self.is_configured(*kind) && !self.provider_order.contains(kind) {
+                anyhow::bail!(
+                    "provider {} is configured but missing from the provider order",
+                    kind.name()
+                );
+            }
         }
+
+        for kind in self.provider_order.iter() {
+            if !self.is_configured(*kind) {
+                continue;
+            }
+
+            match self.load_provider(*kind, &countries) {
+                Ok(provider) => providers.push(provider),
+                Err(err) => {
+                    error!("Failed to load {} provider: {:#}", kind.name(), err);
+                    failed.push(FailedProvider { kind: *kind, err });
+                }
+            }
+        }
+
+        let provider = match providers.len() {
+            0 if !failed.is_empty() => {
+                if failed.len() == 1 {
+                    return Err(failed.pop().expect("length checked").err);
+                }
+
+                anyhow::bail!(
+                    "all configured providers failed to load: {}",
+                    failed
+                        .iter()
+                        .map(|item| format!("{} ({:#})", item.kind.name(), item.err))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            #[cfg(feature = "embedded-dataset")]
+            0 => {
+                log::warn!(
+                    "No database source provided. Falling back to the embedded dataset, which is \
+                    a small snapshot for demo purposes only"
+                );
+                IpgeoProvider::Herrbischoff(
+                    self.herrbischoff_index(HerrbischoffProvider::from_embedded(&countries)?),
+                )
+            }
+            #[cfg(not(feature = "embedded-dataset"))]
+            0 => anyhow::bail!("no valid IP geolocation database source provided"),
+            1 => providers.pop().expect("length checked"),
+            _ => IpgeoProvider::Chained(providers, self.conflict_policy.into()),
+        };
+
+        Ok(LoadedSources { provider, failed })
     }
-}
 
-impl ApiError {
-    pub fn status_code(&self) -> StatusCode {
-        match self {
-            Self::InvalidIpAddress => StatusCode::BAD_REQUEST,
-            Self::IpAddressNotFound => StatusCode::NOT_FOUND,
+    fn herrbischoff_index(&self, provider: HerrbischoffProvider) -> HerrbischoffProvider {
+        match self.index {
+            IndexKind::Sorted => provider,
+            IndexKind::Lpm => provider.with_lpm_index(),
         }
     }
-}
 
-impl ApiResponse for ApiError {
-    fn meta() -> MetaResponses {
-        MetaResponses {
-            responses: vec![MetaResponse {
-                description: "",
-                status: Some(404),
-                content: vec![MetaMediaType {
-                    content_type: "application/json",
-                    schema: ApiErrorResponse::schema_ref(),
-                }],
-                headers: vec![],
-            }],
+    fn load_provider(
+        &self,
+        kind: ProviderKind,
+        countries: &CountryFilter,
+    ) -> anyhow::Result<IpgeoProvider> {
+        Ok(match kind {
+            ProviderKind::Herrbischoff => IpgeoProvider::Herrbischoff(self.herrbischoff_index(
+                HerrbischoffProvider::from_repo(
+                    &self.herrbischoff_path,
+                    countries,
+                    self.herrbischoff_format.into(),
+                )?,
+            )),
+            ProviderKind::Ip2location => {
+                let options = Ip2locationOptions {
+                    mmap: self.mmap,
+                    sort: self.sort_on_load,
+                    countries: countries.clone(),
+                    unknown_country: self.unknown_country,
+                };
+
+                IpgeoProvider::Ip2location(match &self.ip2location_bin {
+                    Some(ip2location_bin) => {
+                        Ip2locationProvider::from_bin(ip2location_bin, &options)?
+                    }
+                    None => {
+                        let ipv4_db = self
+                            .ip2location_db
+                            .as_ref()
+                            .map(|location| location.fetch(self.ip2location_db_sha256.as_deref()))
+                            .transpose()?;
+
+                        let ipv4_db_path = ipv4_db.as_ref().map(|file| file.path());
+                        let ipv6_db_path = self.ip2location_ipv6_db.as_deref();
+
+                        match &self.index_cache {
+                            Some(index_cache) => Ip2locationProvider::from_db_cached(
+                                ipv4_db_path,
+                                ipv6_db_path,
+                                &options,
+                                index_cache,
+                            )?,
+                            None => {
+                                Ip2locationProvider::from_db(ipv4_db_path, ipv6_db_path, &options)?
+                            }
+                        }
+                    }
+                })
+            }
+            ProviderKind::Maxmind => IpgeoProvider::Maxmind(Box::new(MaxmindProvider::from_mmdb(
+                self.maxmind_db
+                    .as_ref()
+                    .expect("already checked that path exists"),
+                countries.clone(),
+            )?)),
+            ProviderKind::Rir => IpgeoProvider::Rir(RirProvider::from_dir(
+                self.rir_path
+                    .as_ref()
+                    .expect("already checked that path exists"),
+                countries,
+            )?),
+        })
+    }
+
+    /// Local files and directories the configured providers are loaded from. Databases downloaded
+    /// from URLs are left out.
+    fn paths(&self) -> Vec<&std::path::Path> {
+        self.herrbischoff_path
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(
+                self.ip2location_db
+                    .as_ref()
+                    .and_then(DatabaseLocation::path),
+            )
+            .chain(
+                [
+                    &self.ip2location_ipv6_db,
+                    &self.ip2location_bin,
+                    &self.maxmind_db,
+                    &self.rir_path,
+                ]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::as_path),
+            )
+            .collect()
+    }
+
+    /// Captures the modification times of the configured paths. Meant to be called right before
+    /// loading, so that the recorded times are never newer than the loaded data.
+    fn dataset_files(&self) -> Vec<DatasetFile> {
+        self.paths()
+            .into_iter()
+            .map(|path| DatasetFile {
+                path: path.to_owned(),
+                modified: latest_modified(path).ok(),
+            })
+            .collect()
+    }
+
+    fn is_configured(&self, kind: ProviderKind) -> bool {
+        match kind {
+            ProviderKind::Herrbischoff => !self.herrbischoff_path.is_empty(),
+            ProviderKind::Ip2location => {
+                self.ip2location_db.is_some()
+                    || self.ip2location_ipv6_db.is_some()
+                    || self.ip2location_bin.is_some()
+            }
+            ProviderKind::Maxmind => self.maxmind_db.is_some(),
+            ProviderKind::Rir => self.rir_path.is_some(),
         }
     }
+}
+
+/// Latest modification time of the file, or of any file under the directory. Hidden entries like
+/// `.git` are skipped as they change independently of the data.
+fn latest_modified(path: &std::path::Path) -> std::io::Result<SystemTime> {
+    let metadata = std::fs::metadata(path)?;
+    let mut latest = metadata.modified()?;
 
-    fn register(registry: &mut Registry) {
-        <ApiErrorResponse as Type>::register(registry);
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            latest = latest.max(latest_modified(&entry.path())?);
+        }
     }
+
+    Ok(latest)
 }
 
-impl From<ApiError> for poem::Error {
-    fn from(value: ApiError) -> Self {
-        let status_code = value.status_code();
-        let response: ApiErrorResponse = value.into();
+/// Reloads the database from the same source whenever SIGHUP is received.
+#[cfg(unix)]
+async fn reload_on_sighup(
+    source: SourceArgs,
+    dataset: Arc<ArcSwap<Dataset>>,
+    failed_providers: Arc<ArcSwap<Vec<FailedProvider>>>,
+    reload_state: Arc<ReloadState>,
+    cache: Option<Arc<LookupCache>>,
+) {
+    let mut signal = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => signal,
+        Err(err) => {
+            error!("Unable to listen for SIGHUP: {}", err);
+            return;
+        }
+    };
 
-        Self::from_response(
-            Response::builder()
-                .status(status_code)
-                .content_type("application/json")
-                .body(response.to_json_string()),
+    while signal.recv().await.is_some() {
+        info!("SIGHUP received, reloading database");
+        reload(
+            &source,
+            &dataset,
+            &failed_providers,
+            &reload_state,
+            cache.as_deref(),
         )
+        .await;
     }
 }
 
-impl From<ApiError> for ApiErrorResponse {
-    fn from(value: ApiError) -> Self {
-        match value {
-            ApiError::InvalidIpAddress => Self {
-                code: 100,
-                message: "Invalid IP address".into(),
-            },
-            ApiError::IpAddressNotFound => Self {
-                code: 101,
-                message: "IP address not covered in database".into(),
-            },
+/// Reloads the database whenever its files change on disk. Bursts of changes, such as those from a
+/// `git pull`, only trigger a single reload once the files have been quiet for `WATCH_DEBOUNCE`.
+fn watch_for_changes(
+    source: SourceArgs,
+    dataset: Arc<ArcSwap<Dataset>>,
+    failed_providers: Arc<ArcSwap<Vec<FailedProvider>>>,
+    reload_state: Arc<ReloadState>,
+    cache: Option<Arc<LookupCache>>,
+) -> anyhow::Result<()> {
+    let targets = source
+        .paths()
+        .into_iter()
+        .map(|path| {
+            std::fs::canonicalize(path)
+                .map_err(|err| anyhow::anyhow!("unable to watch {}: {}", path.display(), err))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let event_targets = targets.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            // Access events are ignored as reloading itself would trigger them
+            Ok(event)
+                if event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove() =>
+            {
+                if event
+                    .paths
+                    .iter()
+                    .any(|path| event_targets.iter().any(|target| path.starts_with(target)))
+                {
+                    let _ = sender.send(());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => error!("Error watching database files: {}", err),
         }
+    })?;
+
+    for target in targets.iter() {
+        if target.is_dir() {
+            watcher.watch(target, RecursiveMode::Recursive)?;
+        } else {
+            // Files are commonly updated by renaming a new file over them, which a watch on the
+            // file itself doesn't survive
+            let parent = target.parent().unwrap_or(target);
+            watcher.watch(parent, RecursiveMode::NonRecursive)?;
+        }
+        info!("Watching {} for changes", target.display());
     }
+
+    tokio::spawn(async move {
+        // The watcher stops once dropped
+        let _watcher = watcher;
+
+        while receiver.recv().await.is_some() {
+            loop {
+                match tokio::time::timeout(WATCH_DEBOUNCE, receiver.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            info!("Database files changed, reloading database");
+            reload(
+                &source,
+                &dataset,
+                &failed_providers,
+                &reload_state,
+                cache.as_deref(),
+            )
+            .await;
+        }
+    });
+
+    Ok(())
+}
+
+/// Replaces the active provider with one freshly loaded from `source`. The previous provider stays
+/// active if any of the configured providers fails to load, so that a broken update of one source
+/// can't take down another one that's being served.
+async fn reload(
+    source: &SourceArgs,
+    dataset: &ArcSwap<Dataset>,
+    failed_providers: &ArcSwap<Vec<FailedProvider>>,
+    reload_state: &ReloadState,
+    cache: Option<&LookupCache>,
+) {
+    let source = source.clone();
+    reload_state.in_progress.fetch_add(1, Ordering::Relaxed);
+    let result = tokio::task::spawn_blocking(move || {
+        let files = source.dataset_files();
+        anyhow::Ok(Dataset {
+            provider: source.load()?.require_all()?,
+            files,
+        })
+    })
+    .await;
+    reload_state.in_progress.fetch_sub(1, Ordering::Relaxed);
+
+    let error = match result {
+        Ok(Ok(new_dataset)) => {
+            dataset.store(Arc::new(new_dataset));
+            failed_providers.store(Arc::new(vec![]));
+            reload_state.last_error.store(None);
+            // Must happen after swapping so that the cache can't be refilled from the old
+            // dataset
+            if let Some(cache) = cache {
+                cache.clear();
+            }
+            info!("Database reloaded");
+            return;
+        }
+        Ok(Err(err)) => format!("{:#}", err),
+        Err(err) => err.to_string(),
+    };
+
+    error!(
+        "Failed to reload database, still serving the previous dataset: {}",
+        error
+    );
+    reload_state.last_error.store(Some(Arc::new(ReloadError {
+        failed_at: SystemTime::now(),
+        error,
+    })));
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+/// Accepts a hex-encoded SHA-256 digest, normalized to lowercase.
+fn parse_sha256(value: &str) -> anyhow::Result<String> {
+    if value.len() != 64 || !value.chars().all(|item| item.is_ascii_hexdigit()) {
+        anyhow::bail!("expected 64 hexadecimal characters");
+    }
+
+    Ok(value.to_ascii_lowercase())
+}
+
+/// Accepts a bare origin (`scheme://host[:port]`), as sent in the `Origin` header. Anything with a
+/// path or query would never match a request and most likely is a configuration mistake.
+fn parse_cors_origin(value: &str) -> anyhow::Result<HeaderValue> {
+    let uri = Uri::from_str(value)?;
+    if uri.scheme().is_none()
+        || uri.authority().is_none()
+        || value.trim_end_matches('/') != value
+        || uri
+            .path_and_query()
+            .is_some_and(|item| item.as_str() != "/")
+    {
+        anyhow::bail!("expected an origin like https://example.com");
+    }
+
+    Ok(HeaderValue::from_str(value)?)
+}
+
+/// Parses the zone as a fully qualified name, so that it doesn't matter whether the trailing dot
+/// is given.
+fn parse_dns_zone(value: &str) -> anyhow::Result<hickory_proto::rr::Name> {
+    let mut zone = hickory_proto::rr::Name::from_str(value)?.to_lowercase();
+    zone.set_fqdn(true);
+
+    Ok(zone)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "ipgeo=debug,poem=debug");
     }
-    env_logger::init();
-
     let cli = Cli::parse();
 
-    let provider = match (cli.herrbischoff_path, cli.ip2location_db) {
-        (Some(herrbischoff_path), None) => {
-            IpgeoProvider::Herrbischoff(HerrbischoffProvider::from_repo(&herrbischoff_path)?)
+    logging::init(cli.log_format);
+
+    match cli.command {
+        Some(Command::Check(source)) => check(&source)?,
+        Some(Command::Lookup(args)) => return lookup(&args),
+        Some(Command::LookupFile(args)) => lookup_file(&args)?,
+        Some(Command::Serve(args)) => run_server(*args)?,
+        None => run_server(cli.serve)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Builds the runtime by hand instead of with `#[tokio::main]` so that the worker thread count can
+/// be configured.
+fn run_server(args: ServeArgs) -> anyhow::Result<()> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(worker_threads) = args.worker_threads {
+        builder.worker_threads(worker_threads.get());
+    }
+
+    builder.build()?.block_on(serve(args))
+}
+
+/// Loads the database the same way as when serving, so that data updates can be validated ahead
+/// of deployment.
+fn check(source: &SourceArgs) -> anyhow::Result<()> {
+    let provider = source.load()?.require_all()?;
+
+    let ipv4_ranges = provider.ipv4_range_count();
+    let ipv6_ranges = provider.ipv6_range_count();
+    println!(
+        "Loaded {} provider with {} IPv4 and {} IPv6 ranges",
+        provider.name(),
+        ipv4_ranges,
+        ipv6_ranges
+    );
+
+    if ipv4_ranges == 0 && ipv6_ranges == 0 {
+        anyhow::bail!("database contains no ranges");
+    }
+
+    Ok(())
+}
+
+/// Resolves a single address the same way as the API does, exiting with a failure status if it's
+/// not found so that it can be used in scripts.
+fn lookup(args: &LookupArgs) -> anyhow::Result<ExitCode> {
+    let provider = args.source.load()?.provider;
+
+    Ok(match match_ip(&provider, args.ip_address) {
+        Some(country_match) => {
+            println!("{}", country_match.country);
+            ExitCode::SUCCESS
+        }
+        None => {
+            println!("not found");
+            ExitCode::FAILURE
+        }
+    })
+}
+
+/// Writes `ip,country` CSV rows for the addresses in the file, with an empty country for
+/// addresses that aren't found and `ERROR` for lines that aren't addresses. Blank lines are
+/// skipped.
+fn lookup_file(args: &LookupFileArgs) -> anyhow::Result<()> {
+    let provider = args.source.load()?.provider;
+
+    let reader: Box<dyn BufRead> = if args.path.as_os_str() == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        let file = std::fs::File::open(&args.path)
+            .map_err(|err| anyhow::anyhow!("unable to open {}: {}", args.path.display(), err))?;
+        Box::new(std::io::BufReader::new(file))
+    };
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout().lock());
+    writer.write_record(["ip", "country"])?;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match IpAddr::from_str(line) {
+            Ok(ip_address) => match match_ip(&provider, ip_address) {
+                Some(country_match) => {
+                    writer.write_record([line, country_match.country.as_str()])?
+                }
+                None => writer.write_record([line, ""])?,
+            },
+            Err(_) => writer.write_record([line, "ERROR"])?,
         }
-        (None, Some(ip2location_db)) => {
-            IpgeoProvider::Ip2location(Ip2locationProvider::from_db(&ip2location_db)?)
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Looks up the address the same way the API does, where reserved addresses are never found.
+fn match_ip(provider: &IpgeoProvider, ip_address: IpAddr) -> Option<CountryMatch> {
+    match ip_address {
+        IpAddr::V4(ip_address) if util::is_reserved_ipv4(&ip_address) => None,
+        IpAddr::V4(ip_address) => provider.match_ipv4(&ip_address).found(),
+        IpAddr::V6(ip_address) if util::is_reserved_ipv6(&ip_address) => None,
+        IpAddr::V6(ip_address) => provider.match_ipv6(&ip_address).found(),
+    }
+}
+
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    // Loading may download databases with a blocking client, which can't run on the runtime
+    let source = args.source.clone();
+    let (dataset_files, loaded) =
+        tokio::task::spawn_blocking(move || (source.dataset_files(), source.load())).await?;
+    let loaded = loaded?;
+    let dataset = Arc::new(ArcSwap::from_pointee(Dataset {
+        provider: loaded.provider,
+        files: dataset_files,
+    }));
+    let failed_providers = Arc::new(ArcSwap::from_pointee(loaded.failed));
+    let reload_state = Arc::new(ReloadState::default());
+
+    let cache = NonZeroUsize::new(args.cache_size).map(|size| Arc::new(LookupCache::new(size)));
+
+    let tracer = match &args.otlp_endpoint {
+        Some(otlp_endpoint) => {
+            info!("Exporting traces to {}", otlp_endpoint);
+            Some(telemetry::init(otlp_endpoint)?)
         }
-        (None, None) => anyhow::bail!("no valid IP geolocation database source provided"),
-        _ => anyhow::bail!("one and only one source should be provided"),
+        None => None,
     };
 
-    let api = Api::new(provider);
-    let api_service = OpenApiService::new(api, "ipgeod", env!("CARGO_PKG_VERSION"));
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(
+        args.source.clone(),
+        dataset.clone(),
+        failed_providers.clone(),
+        reload_state.clone(),
+        cache.clone(),
+    ));
+    if args.watch {
+        watch_for_changes(
+            args.source.clone(),
+            dataset.clone(),
+            failed_providers.clone(),
+            reload_state.clone(),
+            cache.clone(),
+        )?;
+    }
+
+    let metrics = Arc::new(Metrics::new()?);
+
+    let asn = match &args.ip2location_asn {
+        Some(asn_path) => {
+            let asn = AsnDatabase::from_csv(asn_path)?;
+            info!("Loaded {} ASN ranges", asn.range_count());
+            Some(Arc::new(asn))
+        }
+        None => None,
+    };
+
+    let api = Api::new(
+        dataset.clone(),
+        metrics.clone(),
+        cache,
+        asn,
+        args.trust_proxy,
+        args.not_found_status,
+        args.max_range_size,
+    )
+    .with_default_country(args.default_country)
+    .with_country_code_alias(args.country_code_alias);
+    let api_service = OpenApiService::new(api.clone(), "ipgeod", env!("CARGO_PKG_VERSION"));
+
+    let spec_endpoint = api_service.spec_endpoint();
+    // Conventional location most codegen tools probe for
+    let spec_json_endpoint = api_service.spec_endpoint();
+    let swagger_ui = api_service.swagger_ui();
+
+    // Layers added later wrap earlier ones, so rate limiting kicks in before authentication, and
+    // both before queueing for a concurrency slot
+    let mut api_routes = Route::new().at(
+        "/ipv4/batch/stream",
+        post(post_ipv4_batch_stream).data(api.clone()),
+    );
+    if args.enable_dump {
+        api_routes = api_routes.at("/dump/ipv4", get(dump_ipv4).data(dataset.clone()));
+    }
+    let mut api_endpoint = api_routes.nest("/", api_service).map_to_response().boxed();
+    if let Some(max_concurrent) = args.max_concurrent {
+        api_endpoint = api_endpoint
+            .with(ConcurrencyLimit::new(
+                max_concurrent.get(),
+                CONCURRENCY_QUEUE_TIMEOUT,
+                &["/ipv4/batch", "/ipv4/batch/stream", "/dump/ipv4"],
+            ))
+            .boxed();
+    }
+    // The streamed batch endpoint is left out, as it never buffers its body
+    api_endpoint = api_endpoint
+        .with(BodySizeLimit::new(args.max_body_size, &["/ipv4/batch"]))
+        .boxed();
+    // Covers reading request bodies, waiting for a concurrency slot, and sending streamed bodies
+    if let Some(request_timeout) = args.request_timeout {
+        api_endpoint = api_endpoint
+            .with(RequestTimeout::new(
+                Duration::from_secs(request_timeout),
+                &["/ipv4/batch/stream", "/dump/ipv4"],
+            ))
+            .boxed();
+    }
+    // Everything but `/healthz` is gated, so that probes keep working without the key
+    let auth = args.api_key.map(ApiKeyAuth::new);
+    let with_auth = |endpoint: BoxEndpoint<'static>| match &auth {
+        Some(auth) => endpoint.with(auth.clone()).boxed(),
+        None => endpoint,
+    };
+    api_endpoint = with_auth(api_endpoint);
+    if args.compression {
+        api_endpoint = api_endpoint
+            .with(ResponseCompression::new(COMPRESSION_MIN_SIZE))
+            .boxed();
+    }
+    if let Some(rate_limit) = args.rate_limit {
+        api_endpoint = api_endpoint
+            .with(RateLimit::new(rate_limit, args.trust_proxy))
+            .boxed();
+    }
+
+    // Empty lists leave `Cors` allowing anything
+    let cors = Cors::new()
+        .allow_origins(args.cors_allow_origin)
+        .allow_methods(args.cors_allow_method)
+        .allow_headers(args.cors_allow_header);
+
+    if let (Some(dns_port), Some(dns_zone)) = (args.dns_port, args.dns_zone) {
+        let socket = tokio::net::UdpSocket::bind((args.host, dns_port)).await?;
+        info!(
+            "Answering DNS queries for {} on {}",
+            dns_zone,
+            socket.local_addr()?
+        );
+
+        tokio::spawn(dns::serve(socket, dns_zone, dataset.clone()));
+    }
+
+    let mut api_endpoint = api_endpoint.with(cors).with(Tracing).boxed();
+    if let Some(tracer) = tracer {
+        api_endpoint = api_endpoint.with(OpenTelemetryTracing::new(tracer)).boxed();
+    }
 
     let app = Route::new()
-        .nest("/openapi", api_service.spec_endpoint())
-        .nest("/swagger", api_service.swagger_ui())
-        .nest("/", api_service.with(Cors::new()).with(Tracing));
+        .at(
+            "/healthz",
+            get(healthz)
+                .data(dataset.clone())
+                .data(failed_providers)
+                .data(reload_state),
+        )
+        .at(
+            "/version",
+            with_auth(get(version_info).data(dataset).boxed()),
+        )
+        .at(
+            "/metrics",
+            with_auth(get(prometheus_metrics).data(metrics).boxed()),
+        )
+        .at(
+            "/openapi.json",
+            with_auth(spec_json_endpoint.map_to_response().boxed()),
+        )
+        .nest(
+            "/openapi",
+            with_auth(spec_endpoint.map_to_response().boxed()),
+        )
+        .nest("/swagger", with_auth(swagger_ui.map_to_response().boxed()))
+        .nest("/", api_endpoint)
+        .with(RequestId);
 
-    let server = poem::Server::new(TcpListener::bind((Ipv4Addr::new(0, 0, 0, 0), cli.port)));
-    info!("Listening on 0.0.0.0:{}", cli.port);
+    #[cfg(unix)]
+    let listener = match &args.unix_socket {
+        Some(unix_socket) => {
+            remove_stale_socket(unix_socket)?;
+            UnixListener::bind(unix_socket.clone()).boxed()
+        }
+        None => TcpListener::bind((args.host, args.port)).boxed(),
+    };
+    #[cfg(not(unix))]
+    let listener = TcpListener::bind((args.host, args.port)).boxed();
+    let acceptor = match (&args.tls_cert, &args.tls_key) {
+        (Some(tls_cert), Some(tls_key)) => {
+            let read_pem = |path: &std::path::Path| {
+                std::fs::read(path).map_err(|err| {
+                    anyhow::anyhow!("unable to read TLS file {}: {}", path.display(), err)
+                })
+            };
+            let certificate = RustlsCertificate::new()
+                .cert(read_pem(tls_cert)?)
+                .key(read_pem(tls_key)?);
+
+            listener
+                .rustls(RustlsConfig::new().fallback(certificate))
+                .into_acceptor()
+                .await
+                .map_err(|err| anyhow::anyhow!("invalid TLS certificate or key: {}", err))?
+                .boxed()
+        }
+        _ => listener.into_acceptor().await?.boxed(),
+    };
+    for local_addr in acceptor.local_addr() {
+        match local_addr.as_socket_addr() {
+            Some(socket_addr) => info!("Listening on {}", socket_addr),
+            None => info!("Listening on {}", local_addr),
+        }
+    }
+
+    let mut server = poem::Server::new_with_acceptor(acceptor);
+    if let Some(keep_alive_timeout) = args.keep_alive_timeout {
+        server = server.idle_timeout(Duration::from_secs(keep_alive_timeout));
+    }
 
-    server
+    let result = server
         .run_with_graceful_shutdown(
             app,
             async {
@@ -180,9 +1465,48 @@ async fn main() -> anyhow::Result<()> {
                 #[cfg(not(unix))]
                 let _ = tokio::signal::ctrl_c().await;
             },
-            None,
+            Some(Duration::from_secs(args.shutdown_timeout)),
         )
-        .await?;
+        .await;
 
-    Ok(())
+    #[cfg(unix)]
+    if let Some(unix_socket) = &args.unix_socket {
+        if let Err(err) = std::fs::remove_file(unix_socket) {
+            error!("Unable to remove socket {}: {}", unix_socket.display(), err);
+        }
+    }
+
+    telemetry::shutdown().await;
+
+    Ok(result?)
+}
+
+/// Removes a socket file left behind by a previous run that didn't shut down cleanly, which would
+/// otherwise fail binding. Refuses to remove anything other than a socket.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            info!("Removing stale socket {}", path.display());
+            std::fs::remove_file(path)?;
+            Ok(())
+        }
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
 }
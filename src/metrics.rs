@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry};
+
+/// Prometheus metrics for IP lookups.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    lookups: IntCounter,
+    lookup_results: IntCounterVec,
+    lookup_duration: Histogram,
+    cache_lookups: IntCounterVec,
+}
+
+/// Result of a single lookup, used as the `status` label value.
+#[derive(Debug, Clone, Copy)]
+pub enum LookupStatus {
+    Found,
+    NotFound,
+    Invalid,
+}
+
+impl Metrics {
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new();
+
+        let lookups = IntCounter::new("ipgeod_lookups_total", "Total number of lookups")?;
+        let lookup_results = IntCounterVec::new(
+            Opts::new(
+                "ipgeod_lookup_results_total",
+                "Number of lookups by result status",
+            ),
+            &["status"],
+        )?;
+        let lookup_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "ipgeod_lookup_duration_seconds",
+                "Time spent resolving lookups",
+            )
+            .buckets(prometheus::exponential_buckets(0.000_001, 4.0, 10)?),
+        )?;
+
+        let cache_lookups = IntCounterVec::new(
+            Opts::new(
+                "ipgeod_cache_lookups_total",
+                "Number of lookup cache accesses by result",
+            ),
+            &["result"],
+        )?;
+
+        registry.register(Box::new(lookups.clone()))?;
+        registry.register(Box::new(lookup_results.clone()))?;
+        registry.register(Box::new(lookup_duration.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+
+        // Makes sure all statuses are exported even before the first occurrence
+        for status in [
+            LookupStatus::Found,
+            LookupStatus::NotFound,
+            LookupStatus::Invalid,
+        ] {
+            lookup_results.with_label_values(&[status.as_str()]);
+        }
+        for result in ["hit", "miss"] {
+            cache_lookups.with_label_values(&[result]);
+        }
+
+        Ok(Self {
+            registry,
+            lookups,
+            lookup_results,
+            lookup_duration,
+            cache_lookups,
+        })
+    }
+
+    pub fn observe_lookup(&self, status: LookupStatus, duration: Duration) {
+        self.lookups.inc();
+        self.lookup_results
+            .with_label_values(&[status.as_str()])
+            .inc();
+        self.lookup_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_cache_lookup(&self, hit: bool) {
+        self.cache_lookups
+            .with_label_values(&[if hit { "hit" } else { "miss" }])
+            .inc();
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> anyhow::Result<String> {
+        let mut buffer = vec![];
+        prometheus::TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl LookupStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Found => "found",
+            Self::NotFound => "not_found",
+            Self::Invalid => "invalid",
+        }
+    }
+}
@@ -1,73 +1,1136 @@
 use std::{
+    collections::BTreeSet,
+    fmt::{Debug, Display},
     io::{BufRead, BufReader},
-    net::Ipv4Addr,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use cidr::Ipv4Cidr;
+use anyhow::Context;
+use cidr::{Ipv4Cidr, Ipv6Cidr};
+use log::{info, warn};
+use rayon::prelude::*;
+
+use super::{CountryFilter, Ipv4Span, LookupOutcome};
+use crate::country::CountryCode;
+
+mod archive;
+
+mod trie;
+use trie::Ipv4Trie;
 
 #[derive(Debug)]
 pub struct HerrbischoffProvider {
-    cidr_blocks: Vec<CidrBlock>,
+    ipv4_cidr_blocks: Vec<CidrBlock<Ipv4Cidr>>,
+    ipv6_cidr_blocks: Vec<CidrBlock<Ipv6Cidr>>,
+    /// Index over `ipv4_cidr_blocks` used for lookups instead of binary search, if built.
+    ipv4_trie: Option<Ipv4Trie>,
 }
 
 #[derive(Debug)]
-struct CidrBlock {
-    cidr: Ipv4Cidr,
-    country: String,
+struct CidrBlock<C: CidrRange> {
+    cidr: C,
+    start: C::Value,
+    end: C::Value,
+    country: CountryCode,
+    /// Prefix length of the allocation the block belongs to, which is shorter than the one of
+    /// `cidr` for parts left of a block after carving out more specific blocks.
+    prefix_len: u8,
+}
+
+/// A CIDR block lookup match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixMatch {
+    pub country: CountryCode,
+    /// Prefix length of the matched block, e.g. `24` for a `/24` block
+    pub prefix_len: u8,
+}
+
+/// Layout of the lines in `.cidr` files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineFormat {
+    /// One CIDR block per line, with the country taken from the `<cc>.cidr` file name.
+    #[default]
+    Cidr,
+    /// Lines may also carry their own country as `CIDR,CC`, told apart by the comma. Such lines
+    /// are accepted in any `.cidr` file, so that aggregated files like `all.cidr` load alongside
+    /// per-country ones.
+    Mixed,
+}
+
+/// CIDR types that can be expanded into an inclusive numeric `[start, end]` range.
+trait CidrRange: Copy {
+    type Value: Debug + Ord + Copy;
+
+    fn range(&self) -> (Self::Value, Self::Value);
+
+    fn prefix_len(&self) -> u8;
+
+    /// Splits the block into its lower and upper halves. Must not be called on single-address
+    /// blocks.
+    fn split(&self) -> (Self, Self);
 }
 
 impl HerrbischoffProvider {
-    pub fn from_repo(repo_path: &std::path::Path) -> anyhow::Result<Self> {
-        let mut cidr_blocks = vec![];
-
-        for entry in std::fs::read_dir(repo_path.join("ipv4"))? {
-            let entry = entry?;
-            let file_path = entry.path();
-            if file_path.extension().is_some_and(|value| value == "cidr") {
-                let country_code = file_path
-                    .file_name()
-                    .ok_or_else(|| anyhow::anyhow!("unable to read file name"))?
-                    .to_str()
-                    .ok_or_else(|| anyhow::anyhow!("invalid file name"))?
-                    .split_once('.')
-                    .expect("already checked that extension exists")
-                    .0
-                    .to_uppercase();
-
-                if country_code.len() != 2 {
-                    anyhow::bail!("invalid country code: {}", country_code);
+    /// Loads the `ipv4` and `ipv6` directories of the repositories. Each repository must contain at
+    /// least one `.cidr` file in either directory, while the other one can be absent. Paths to
+    /// `.tar.gz`, `.tgz`, `.tar` or `.zip` files are read as release archives of the repository.
+    ///
+    /// Blocks from later repositories take precedence over conflicting blocks from earlier ones,
+    /// so that upstream data can be patched with a separately maintained overlay. Overridden
+    /// blocks are split as needed to leave out just the overriding parts.
+    ///
+    /// Files of countries not allowed by `countries` are skipped without being parsed, unless
+    /// `line_format` lets lines carry their own country.
+    pub fn from_repo(
+        repo_paths: &[PathBuf],
+        countries: &CountryFilter,
+        line_format: LineFormat,
+    ) -> anyhow::Result<Self> {
+        if repo_paths.is_empty() {
+            anyhow::bail!("no country-ip-blocks repo path provided");
+        }
+
+        let mut ipv4_cidr_blocks = vec![];
+        let mut ipv6_cidr_blocks = vec![];
+
+        for repo_path in repo_paths.iter() {
+            let repo = if repo_path.is_file() && archive::is_archive(repo_path) {
+                let (ipv4_files, ipv6_files) = archive::read_cidr_files(repo_path)?;
+                if ipv4_files.is_empty() && ipv6_files.is_empty() {
+                    anyhow::bail!(
+                        "no .cidr files found in {} — is this a country-ip-blocks archive?",
+                        repo_path.display()
+                    );
+                }
+
+                Self::from_readers(ipv4_files, ipv6_files, countries, line_format)?
+            } else {
+                let ipv4_dir = repo_path.join("ipv4");
+                let ipv6_dir = repo_path.join("ipv6");
+
+                let has_ipv4 = ipv4_dir.is_dir() && has_cidr_files(&ipv4_dir)?;
+                let has_ipv6 = ipv6_dir.is_dir() && has_cidr_files(&ipv6_dir)?;
+                if !has_ipv4 && !has_ipv6 {
+                    anyhow::bail!(
+                        "no .cidr files found under {} — is this the country-ip-blocks repo?",
+                        repo_path.display()
+                    );
+                }
+
+                Self::from_readers(
+                    if has_ipv4 {
+                        open_cidr_files(&ipv4_dir)?
+                    } else {
+                        vec![]
+                    },
+                    if has_ipv6 {
+                        open_cidr_files(&ipv6_dir)?
+                    } else {
+                        vec![]
+                    },
+                    countries,
+                    line_format,
+                )?
+            };
+
+            info!(
+                "Loaded {} IPv4 and {} IPv6 CIDR blocks from {}",
+                repo.ipv4_cidr_blocks.len(),
+                repo.ipv6_cidr_blocks.len(),
+                repo_path.display()
+            );
+
+            ipv4_cidr_blocks = overlay_cidr_blocks(
+                ipv4_cidr_blocks,
+                repo.ipv4_cidr_blocks,
+                |block, overridden| log_repo_override(repo_path, block, overridden),
+            );
+            ipv6_cidr_blocks = overlay_cidr_blocks(
+                ipv6_cidr_blocks,
+                repo.ipv6_cidr_blocks,
+                |block, overridden| log_repo_override(repo_path, block, overridden),
+            );
+        }
+
+        Ok(Self {
+            ipv4_cidr_blocks,
+            ipv6_cidr_blocks,
+            ipv4_trie: None,
+        })
+    }
+
+    /// Loads `.cidr` file contents from arbitrary sources, each paired with the name or path of the
+    /// file it comes from, e.g. `ipv4/us.cidr`. The country code is taken from the file name, and
+    /// the name is used in error messages. Files not named like `<cc>.cidr` are skipped with a
+    /// warning, unless `line_format` is [`LineFormat::Mixed`], in which case all of their lines
+    /// must carry a country.
+    ///
+    /// Files are parsed in parallel, one file per task.
+    pub fn from_readers<R>(
+        ipv4_files: Vec<(String, R)>,
+        ipv6_files: Vec<(String, R)>,
+        countries: &CountryFilter,
+        line_format: LineFormat,
+    ) -> anyhow::Result<Self>
+    where
+        R: BufRead + Send,
+    {
+        Ok(Self {
+            ipv4_cidr_blocks: parse_cidr_files(ipv4_files, countries, line_format)?,
+            ipv6_cidr_blocks: parse_cidr_files(ipv6_files, countries, line_format)?,
+            ipv4_trie: None,
+        })
+    }
+
+    /// Loads in-memory `.cidr` file contents, each paired with the two-letter country code it
+    /// belongs to.
+    pub fn from_bytes<'a>(
+        ipv4_files: &[(&str, &'a [u8])],
+        ipv6_files: &[(&str, &'a [u8])],
+        countries: &CountryFilter,
+    ) -> anyhow::Result<Self> {
+        let named_files = |family: &str, files: &[(&str, &'a [u8])]| {
+            files
+                .iter()
+                .map(|(country_code, content)| {
+                    (format!("{}/{}.cidr", family, country_code), *content)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        Self::from_readers(
+            named_files("ipv4", ipv4_files),
+            named_files("ipv6", ipv6_files),
+            countries,
+            LineFormat::Cidr,
+        )
+    }
+
+    /// Loads the small snapshot bundled into the binary with the `embedded-dataset` feature. It only
+    /// covers a handful of well-known allocations and is meant as a convenience fallback for trying
+    /// out the daemon, not as an authoritative source.
+    #[cfg(feature = "embedded-dataset")]
+    pub fn from_embedded(countries: &CountryFilter) -> anyhow::Result<Self> {
+        macro_rules! embedded {
+            ($family:literal, $country:literal) => {
+                (
+                    $country,
+                    include_bytes!(concat!(
+                        env!("CARGO_MANIFEST_DIR"),
+                        "/data/embedded/",
+                        $family,
+                        "/",
+                        $country,
+                        ".cidr"
+                    ))
+                    .as_slice(),
+                )
+            };
+        }
+
+        Self::from_bytes(
+            &[
+                embedded!("ipv4", "au"),
+                embedded!("ipv4", "cn"),
+                embedded!("ipv4", "de"),
+                embedded!("ipv4", "gb"),
+                embedded!("ipv4", "jp"),
+                embedded!("ipv4", "us"),
+            ],
+            &[embedded!("ipv6", "de"), embedded!("ipv6", "us")],
+            countries,
+        )
+    }
+
+    /// Builds a prefix trie over the IPv4 blocks to look addresses up in, instead of binary
+    /// searching the sorted blocks. The trie takes more memory, but needs at most three reads per
+    /// lookup regardless of the number of blocks, which tends to pay off with many small blocks.
+    pub fn with_lpm_index(mut self) -> Self {
+        let trie = Ipv4Trie::new(
+            self.ipv4_cidr_blocks
+                .iter()
+                .map(|block| (block.start, block.cidr.network_length())),
+        );
+        info!(
+            "Built prefix trie over {} IPv4 blocks",
+            self.ipv4_cidr_blocks.len()
+        );
+
+        self.ipv4_trie = Some(trie);
+        self
+    }
+
+    pub fn ipv4_range_count(&self) -> usize {
+        self.ipv4_cidr_blocks.len()
+    }
+
+    pub fn ipv6_range_count(&self) -> usize {
+        self.ipv6_cidr_blocks.len()
+    }
+
+    /// Lists the distinct countries with any IPv4 or IPv6 blocks, sorted by code.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        let countries = self
+            .ipv4_cidr_blocks
+            .iter()
+            .map(|block| block.country)
+            .chain(self.ipv6_cidr_blocks.iter().map(|block| block.country))
+            .collect::<BTreeSet<_>>();
+
+        countries.into_iter().collect()
+    }
+
+    /// Lists the IPv4 CIDR blocks assigned to the country.
+    pub fn get_ipv4_cidrs(&self, country: CountryCode) -> Vec<Ipv4Cidr> {
+        self.ipv4_cidr_blocks
+            .iter()
+            .filter(|block| block.country == country)
+            .map(|block| block.cidr)
+            .collect()
+    }
+
+    pub fn get_ipv4_spans(&self, start: u32, end: u32) -> Vec<Ipv4Span> {
+        // Blocks are disjoint and sorted, so their `end` values are sorted too
+        let first = self
+            .ipv4_cidr_blocks
+            .partition_point(|block| block.end < start);
+
+        self.ipv4_cidr_blocks[first..]
+            .iter()
+            .take_while(|block| block.start <= end)
+            .map(|block| Ipv4Span {
+                start: block.start.max(start),
+                end: block.end.min(end),
+                country: block.country,
+            })
+            .collect()
+    }
+
+    /// Finds the CIDR block containing the IPv4 address.
+    pub fn get_ipv4_range(&self, ip_address: &Ipv4Addr) -> LookupOutcome<Ipv4Span> {
+        self.find_ipv4_block(ip_address).map(|block| Ipv4Span {
+            start: block.start,
+            end: block.end,
+            country: block.country,
+        })
+    }
+
+    /// Finds the most specific CIDR block containing the IPv4 address.
+    pub fn get_ipv4_match(&self, ip_address: &Ipv4Addr) -> LookupOutcome<PrefixMatch> {
+        self.find_ipv4_block(ip_address).map(|block| PrefixMatch {
+            country: block.country,
+            prefix_len: block.prefix_len,
+        })
+    }
+
+    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> LookupOutcome<CountryCode> {
+        self.get_ipv4_match(ip_address).map(|item| item.country)
+    }
+
+    pub fn get_ipv6_country(&self, ip_address: &Ipv6Addr) -> LookupOutcome<CountryCode> {
+        find_block(&self.ipv6_cidr_blocks, u128::from(*ip_address)).map(|block| block.country)
+    }
+
+    fn find_ipv4_block(&self, ip_address: &Ipv4Addr) -> LookupOutcome<&CidrBlock<Ipv4Cidr>> {
+        let ip_value = u32::from(*ip_address);
+
+        let Some(trie) = &self.ipv4_trie else {
+            return find_block(&self.ipv4_cidr_blocks, ip_value);
+        };
+        if let Some(ind) = trie.find(ip_value) {
+            return LookupOutcome::Found(&self.ipv4_cidr_blocks[ind]);
+        }
+
+        // Misses are told apart the same way as with binary search
+        match (self.ipv4_cidr_blocks.first(), self.ipv4_cidr_blocks.last()) {
+            (Some(first), Some(last)) if ip_value >= first.start && ip_value <= last.end => {
+                LookupOutcome::InGap
+            }
+            (Some(_), Some(last)) if ip_value > last.end => LookupOutcome::AboveRange,
+            _ => LookupOutcome::BelowRange,
+        }
+    }
+}
+
+impl CidrRange for Ipv4Cidr {
+    type Value = u32;
+
+    fn range(&self) -> (u32, u32) {
+        (
+            u32::from(self.first_address()),
+            u32::from(self.last_address()),
+        )
+    }
+
+    fn prefix_len(&self) -> u8 {
+        self.network_length()
+    }
+
+    fn split(&self) -> (Self, Self) {
+        let len = self.network_length() + 1;
+        let start = u32::from(self.first_address());
+        let upper_start = start + (1 << (32 - len));
+
+        (
+            Ipv4Cidr::new(start.into(), len).expect("halves of a valid block are valid"),
+            Ipv4Cidr::new(upper_start.into(), len).expect("halves of a valid block are valid"),
+        )
+    }
+}
+
+impl CidrRange for Ipv6Cidr {
+    type Value = u128;
+
+    fn range(&self) -> (u128, u128) {
+        (
+            u128::from(self.first_address()),
+            u128::from(self.last_address()),
+        )
+    }
+
+    fn prefix_len(&self) -> u8 {
+        self.network_length()
+    }
+
+    fn split(&self) -> (Self, Self) {
+        let len = self.network_length() + 1;
+        let start = u128::from(self.first_address());
+        let upper_start = start + (1 << (128 - len));
+
+        (
+            Ipv6Cidr::new(start.into(), len).expect("halves of a valid block are valid"),
+            Ipv6Cidr::new(upper_start.into(), len).expect("halves of a valid block are valid"),
+        )
+    }
+}
+
+/// Byte order mark some editors and mirrors prepend to text, which isn't considered whitespace.
+const BOM: char = '\u{feff}';
+
+/// Whether the file has a `.cidr` extension, in any letter case.
+fn is_cidr_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|value| value.eq_ignore_ascii_case("cidr"))
+}
+
+/// Takes the country code from a `<cc>.cidr` file name, ignoring surrounding whitespace and byte
+/// order marks around the code.
+fn file_country_code(file_name: &str) -> Option<CountryCode> {
+    let (country_code, extension) = Path::new(file_name)
+        .file_name()
+        .and_then(|value| value.to_str())?
+        .split_once('.')?;
+    if !extension.eq_ignore_ascii_case("cidr") {
+        return None;
+    }
+
+    CountryCode::new(country_code.trim_matches(|c: char| c.is_whitespace() || c == BOM))
+}
+
+fn has_cidr_files(dir: &Path) -> anyhow::Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        if is_cidr_file(&entry?.path()) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Opens the `.cidr` files in the directory, each paired with its path.
+fn open_cidr_files(dir: &Path) -> anyhow::Result<Vec<(String, BufReader<std::fs::File>)>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let file_path = entry?.path();
+        if is_cidr_file(&file_path) {
+            let file = std::fs::File::open(&file_path)
+                .with_context(|| format!("failed to open {}", file_path.display()))?;
+            files.push((file_path.display().to_string(), BufReader::new(file)));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Parses the `.cidr` files in parallel, one file per task.
+fn parse_cidr_files<C, R>(
+    files: Vec<(String, R)>,
+    countries: &CountryFilter,
+    line_format: LineFormat,
+) -> anyhow::Result<Vec<CidrBlock<C>>>
+where
+    C: CidrRange + FromStr + Display + Send,
+    C::Value: Send,
+    C::Err: std::error::Error + Send + Sync + 'static,
+    R: BufRead + Send,
+{
+    let file_blocks = files
+        .into_par_iter()
+        .map(|(file_name, reader)| {
+            let mut cidr_blocks = vec![];
+
+            let file_country = file_country_code(&file_name);
+            match (file_country, line_format) {
+                (None, LineFormat::Cidr) => {
+                    warn!(
+                        "Skipping {}: file name doesn't match the <cc>.cidr pattern",
+                        file_name
+                    );
+                    return Ok(cidr_blocks);
+                }
+                // Nothing else in the file can be of another country
+                (Some(country_code), LineFormat::Cidr) if !countries.allows(country_code) => {
+                    return Ok(cidr_blocks);
+                }
+                _ => {}
+            }
+
+            parse_cidr_lines(
+                &file_name,
+                file_country,
+                reader,
+                countries,
+                line_format,
+                &mut cidr_blocks,
+            )?;
+
+            Ok(cidr_blocks)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    sort_cidr_blocks(file_blocks.into_iter().flatten().collect())
+}
+
+/// Parses the lines of a single `.cidr` file, skipping blank lines and `#` comments. Lines are
+/// assigned `file_country` unless they carry their own. `source_name` identifies the file in error
+/// messages.
+fn parse_cidr_lines<C, R>(
+    source_name: &str,
+    file_country: Option<CountryCode>,
+    reader: R,
+    countries: &CountryFilter,
+    line_format: LineFormat,
+    cidr_blocks: &mut Vec<CidrBlock<C>>,
+) -> anyhow::Result<()>
+where
+    C: CidrRange + FromStr,
+    C::Err: std::error::Error + Send + Sync + 'static,
+    R: BufRead,
+{
+    for (ind, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("failed to read {}:{}", source_name, ind + 1))?;
+        let line = line.trim_matches(|c: char| c.is_whitespace() || c == BOM);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (line, country_code) = match line.split_once(',') {
+            Some((cidr, country_code)) if line_format == LineFormat::Mixed => {
+                let country_code = CountryCode::new(country_code.trim()).with_context(|| {
+                    format!(
+                        "invalid country code at {}:{}: {:?}",
+                        source_name,
+                        ind + 1,
+                        country_code
+                    )
+                })?;
+                (cidr.trim(), country_code)
+            }
+            _ => match file_country {
+                Some(country_code) => (line, country_code),
+                None => anyhow::bail!("missing country at {}:{}: {:?}", source_name, ind + 1, line),
+            },
+        };
+        if !countries.allows(country_code) {
+            continue;
+        }
+
+        let cidr: C = line.parse().with_context(|| {
+            format!(
+                "failed to parse CIDR at {}:{}: {:?}",
+                source_name,
+                ind + 1,
+                line
+            )
+        })?;
+        let (start, end) = cidr.range();
+
+        cidr_blocks.push(CidrBlock {
+            cidr,
+            start,
+            end,
+            country: country_code,
+            prefix_len: cidr.prefix_len(),
+        })
+    }
+
+    Ok(())
+}
+
+/// Sorts the blocks and resolves overlaps between them. Where blocks nest, the most specific one
+/// wins for the addresses it covers, like longest-prefix matching would.
+fn sort_cidr_blocks<C>(mut cidr_blocks: Vec<CidrBlock<C>>) -> anyhow::Result<Vec<CidrBlock<C>>>
+where
+    C: CidrRange + Display,
+{
+    // Sorts by `start`, with larger blocks first when two blocks share the same `start`. Since CIDR
+    // blocks either nest or are disjoint, any overlap found below means the later block is fully
+    // contained in the earlier one, or is the same block.
+    cidr_blocks.sort_unstable_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let cidr_blocks = dedupe_cidr_blocks(cidr_blocks)?;
+
+    let mut outer_blocks: Vec<CidrBlock<C>> = Vec::with_capacity(cidr_blocks.len());
+    let mut nested_blocks = vec![];
+    for block in cidr_blocks.into_iter() {
+        match outer_blocks.last() {
+            Some(last_block) if last_block.end >= block.start => nested_blocks.push(block),
+            _ => outer_blocks.push(block),
+        }
+    }
+
+    // Nested blocks are rare, so they're layered on top one prefix length at a time, from the least
+    // specific to the most specific
+    nested_blocks.sort_unstable_by_key(|block| (block.prefix_len, block.start));
+    let mut nested_blocks = nested_blocks.into_iter().peekable();
+    let mut sorted_blocks = outer_blocks;
+    while let Some(first_block) = nested_blocks.next() {
+        let prefix_len = first_block.prefix_len;
+        let mut level = vec![first_block];
+        while let Some(block) = nested_blocks.next_if(|block| block.prefix_len == prefix_len) {
+            level.push(block);
+        }
+
+        sorted_blocks = overlay_cidr_blocks(sorted_blocks, level, |block, overridden| {
+            warn!(
+                "CIDR block {} ({}) overrides less specific block {} ({})",
+                block.cidr, block.country, overridden.cidr, overridden.country
+            )
+        });
+    }
+
+    Ok(sorted_blocks)
+}
+
+/// Drops repeated blocks, as well as blocks nested right within a block of the same country, from
+/// sorted blocks. Such blocks don't change any lookup result other than making it look more
+/// specific than the data is. Fails on repeated blocks of different countries, which can't be told
+/// apart by specificity.
+fn dedupe_cidr_blocks<C>(cidr_blocks: Vec<CidrBlock<C>>) -> anyhow::Result<Vec<CidrBlock<C>>>
+where
+    C: CidrRange + Display,
+{
+    let mut sorted_blocks: Vec<CidrBlock<C>> = Vec::with_capacity(cidr_blocks.len());
+    // Indices of the kept blocks enclosing the current one, from the outermost to the innermost
+    let mut enclosing: Vec<usize> = vec![];
+    // Repeated blocks are adjacent, but the earlier one might have been dropped already
+    let mut previous_block: Option<(C, CountryCode)> = None;
+    let mut redundant_count = 0;
+
+    for block in cidr_blocks.into_iter() {
+        if let Some((previous_cidr, previous_country)) = previous_block {
+            if previous_cidr.range() == (block.start, block.end)
+                && previous_country != block.country
+            {
+                anyhow::bail!(
+                    "same CIDR block listed for different countries: {} ({}) and {} ({})",
+                    previous_cidr,
+                    previous_country,
+                    block.cidr,
+                    block.country
+                );
+            }
+        }
+        previous_block = Some((block.cidr, block.country));
+
+        while let Some(&ind) = enclosing.last() {
+            if sorted_blocks[ind].end >= block.start {
+                break;
+            }
+            enclosing.pop();
+        }
+
+        // Redundant block listed more than once, or within a block of the same country
+        if let Some(&ind) = enclosing.last() {
+            if sorted_blocks[ind].country == block.country {
+                redundant_count += 1;
+                continue;
+            }
+        }
+
+        enclosing.push(sorted_blocks.len());
+        sorted_blocks.push(block);
+    }
+
+    if redundant_count > 0 {
+        info!(
+            "Dropped {} duplicate or redundant CIDR blocks",
+            redundant_count
+        );
+    }
+
+    Ok(sorted_blocks)
+}
+
+/// Merges the sorted `overlay` blocks into the sorted `base` blocks. Parts of `base` blocks covered
+/// by `overlay` blocks are replaced, calling `on_override` with the overriding and the overridden
+/// block if their countries differ.
+fn overlay_cidr_blocks<C, F>(
+    base: Vec<CidrBlock<C>>,
+    overlay: Vec<CidrBlock<C>>,
+    on_override: F,
+) -> Vec<CidrBlock<C>>
+where
+    C: CidrRange + Display,
+    F: Fn(&CidrBlock<C>, &CidrBlock<C>),
+{
+    if base.is_empty() {
+        return overlay;
+    }
+
+    let mut merged_blocks = Vec::with_capacity(base.len() + overlay.len());
+    for block in base.into_iter() {
+        let first = overlay.partition_point(|item| item.end < block.start);
+        let overrides = overlay[first..]
+            .iter()
+            .take_while(|item| item.start <= block.end)
+            .collect::<Vec<_>>();
+
+        if overrides.is_empty() {
+            merged_blocks.push(block);
+            continue;
+        }
+
+        for item in overrides.iter() {
+            if item.country != block.country {
+                on_override(item, &block);
+            }
+        }
+
+        // Carved out parts still count as matches of the original block
+        for cidr in subtract_cidr(block.cidr, &overrides) {
+            let (start, end) = cidr.range();
+            merged_blocks.push(CidrBlock {
+                cidr,
+                start,
+                end,
+                country: block.country,
+                prefix_len: block.prefix_len,
+            });
+        }
+    }
+    merged_blocks.extend(overlay);
+
+    // Neither side overlaps itself, and overlaps across sides are carved out
+    merged_blocks.sort_unstable_by_key(|block| block.start);
+    merged_blocks
+}
+
+/// Splits `cidr` into the largest blocks not covered by any of `holes`.
+fn subtract_cidr<C>(cidr: C, holes: &[&CidrBlock<C>]) -> Vec<C>
+where
+    C: CidrRange,
+{
+    let (start, end) = cidr.range();
+    let mut overlapping = holes
+        .iter()
+        .filter(|hole| hole.start <= end && hole.end >= start)
+        .peekable();
+
+    if overlapping.peek().is_none() {
+        return vec![cidr];
+    }
+    // CIDR blocks either nest or are disjoint, so the block is either fully covered or strictly
+    // larger than the overlapping holes
+    if overlapping.any(|hole| hole.start <= start && hole.end >= end) {
+        return vec![];
+    }
+
+    let (lower, upper) = cidr.split();
+    let mut cidrs = subtract_cidr(lower, holes);
+    cidrs.extend(subtract_cidr(upper, holes));
+    cidrs
+}
+
+fn log_repo_override<C>(repo_path: &Path, block: &CidrBlock<C>, overridden: &CidrBlock<C>)
+where
+    C: CidrRange + Display,
+{
+    warn!(
+        "CIDR block {} ({}) from {} overrides {} ({})",
+        block.cidr,
+        block.country,
+        repo_path.display(),
+        overridden.cidr,
+        overridden.country
+    );
+}
+
+fn find_block<C>(blocks: &[CidrBlock<C>], ip_value: C::Value) -> LookupOutcome<&CidrBlock<C>>
+where
+    C: CidrRange,
+{
+    match blocks.binary_search_by(|block| block.start.cmp(&ip_value)) {
+        Ok(ind) => {
+            // `start` matches perfectly with `ip_value`
+            LookupOutcome::Found(&blocks[ind])
+        }
+        Err(ind) => {
+            if ind > 0 {
+                // No exact `start` matches. This is the closest block
+                let block = &blocks[ind - 1];
+
+                if block.end >= ip_value {
+                    // The closest block includes `ip_value`
+                    LookupOutcome::Found(block)
+                } else if ind < blocks.len() {
+                    // `ip_value` falls in the gap between two blocks
+                    LookupOutcome::InGap
+                } else {
+                    // `ip_value` is larger even than the last block
+                    LookupOutcome::AboveRange
                 }
+            } else {
+                // `ip_value` is smaller even than the first block
+                LookupOutcome::BelowRange
+            }
+        }
+    }
+}
 
-                let mut file = std::fs::File::open(&file_path)?;
-                let reader = BufReader::new(&mut file);
-                for line in reader.lines() {
-                    let line = line?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
 
-                    let cidr: Ipv4Cidr = line.parse()?;
+    /// Countries expected at the given addresses, or `None` if loading is expected to fail.
+    type ExpectedCountries = Option<&'static [(&'static str, &'static str)]>;
+
+    /// `.cidr` file names paired with their contents.
+    type CidrFiles = &'static [(&'static str, &'static str)];
+
+    fn lookup(provider: &HerrbischoffProvider, ip_address: &str) -> Option<(String, u8)> {
+        provider
+            .get_ipv4_match(&ip_address.parse().unwrap())
+            .found()
+            .map(|item| (item.country.to_string(), item.prefix_len))
+    }
 
-                    cidr_blocks.push(CidrBlock {
-                        cidr,
-                        country: country_code.clone(),
-                    })
+    fn found(country: &str, prefix_len: u8) -> Option<(String, u8)> {
+        Some((country.to_owned(), prefix_len))
+    }
+
+    #[test]
+    fn test_parse_files() {
+        let cases: [(&str, CidrFiles, ExpectedCountries); 8] = [
+            (
+                "unsorted lines",
+                &[("ipv4/us.cidr", "10.2.0.0/16\n10.0.0.0/16\n")],
+                Some(&[("10.0.0.0", "US"), ("10.2.0.0", "US")]),
+            ),
+            (
+                "comments and blank lines",
+                &[("ipv4/us.cidr", "# header\n\n  10.0.0.0/16  \n")],
+                Some(&[("10.0.0.0", "US")]),
+            ),
+            (
+                "country code from nested path",
+                &[("/path/to/repo/ipv4/de.cidr", "10.0.0.0/16\n")],
+                Some(&[("10.0.0.0", "DE")]),
+            ),
+            (
+                "malformed line",
+                &[("ipv4/us.cidr", "10.0.0.0/16\n10.0.0.0/33\n")],
+                None,
+            ),
+            ("host bits set", &[("ipv4/us.cidr", "10.0.0.1/16\n")], None),
+            (
+                "invalid country code skipped",
+                &[
+                    ("ipv4/usa.cidr", "10.0.0.0/16\n"),
+                    ("ipv4/de.cidr", "10.1.0.0/16\n"),
+                ],
+                Some(&[("10.1.0.0", "DE")]),
+            ),
+            (
+                "uppercase extension",
+                &[("ipv4/US.CIDR", "10.0.0.0/16\n")],
+                Some(&[("10.0.0.0", "US")]),
+            ),
+            (
+                "byte order marks and whitespace",
+                &[("ipv4/\u{feff}us .cidr", "\u{feff}10.0.0.0/16\n")],
+                Some(&[("10.0.0.0", "US")]),
+            ),
+        ];
+        for (name, files, expected) in cases {
+            let result = HerrbischoffProvider::from_readers(
+                files
+                    .iter()
+                    .map(|(file_name, content)| (file_name.to_string(), content.as_bytes()))
+                    .collect(),
+                vec![],
+                &CountryFilter::default(),
+                LineFormat::Cidr,
+            );
+            match expected {
+                Some(expected) => {
+                    let provider = result.unwrap_or_else(|err| panic!("{}: {}", name, err));
+                    for (ip_address, country) in expected {
+                        assert_eq!(
+                            lookup(&provider, ip_address).map(|(country, _)| country),
+                            Some(country.to_string()),
+                            "{}",
+                            name
+                        );
+                    }
                 }
+                None => assert!(result.is_err(), "{}", name),
             }
         }
+    }
 
-        Ok(Self { cidr_blocks })
+    #[test]
+    fn test_longest_prefix_match() {
+        let provider = HerrbischoffProvider::from_bytes(
+            &[
+                ("us", b"10.0.0.0/8\n10.1.2.0/24\n".as_slice()),
+                ("de", b"10.1.0.0/16\n".as_slice()),
+            ],
+            &[],
+            &CountryFilter::default(),
+        )
+        .unwrap();
+
+        assert_eq!(lookup(&provider, "10.0.0.0"), found("US", 8));
+        assert_eq!(lookup(&provider, "10.1.0.0"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.1.1.255"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.1.2.0"), found("US", 24));
+        assert_eq!(lookup(&provider, "10.1.2.255"), found("US", 24));
+        assert_eq!(lookup(&provider, "10.1.3.0"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.1.255.255"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.2.0.0"), found("US", 8));
+        assert_eq!(lookup(&provider, "10.255.255.255"), found("US", 8));
+        assert_eq!(lookup(&provider, "11.0.0.0"), None);
     }
 
-    // This implementation is extremely inefficient, with O(n) for each lookup. This can be
-    // optimized with a sorted list of CIDR blocks, and use binary search to reduce the steps to
-    // O(log n). Though slow and inefficient, it's good enough for an MVP.
-    //
-    // TODO: optimize with sorted CIDR blocks and binary search.
-    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> Option<String> {
-        for block in self.cidr_blocks.iter() {
-            if block.cidr.contains(ip_address) {
-                return Some(block.country.clone());
+    #[test]
+    fn test_lpm_index() {
+        let files: &[(&str, &[u8])] = &[
+            ("us", b"10.0.0.0/8\n10.1.2.0/24\n6.0.0.0/7\n"),
+            ("de", b"10.1.0.0/16\n10.1.2.128/32\n"),
+            ("jp", b"1.0.0.0/24\n1.0.2.0/23\n192.168.0.0/20\n"),
+            ("au", b"255.255.255.255/32\n"),
+        ];
+        let sorted =
+            HerrbischoffProvider::from_bytes(files, &[], &CountryFilter::default()).unwrap();
+        let lpm = HerrbischoffProvider::from_bytes(files, &[], &CountryFilter::default())
+            .unwrap()
+            .with_lpm_index();
+
+        // Both ends of every block and their neighbors, covering misses of every kind
+        let mut ip_values = vec![0, 1, u32::MAX - 1, u32::MAX];
+        for block in sorted.ipv4_cidr_blocks.iter() {
+            for ip_value in [block.start, block.end] {
+                ip_values.extend([
+                    ip_value.saturating_sub(1),
+                    ip_value,
+                    ip_value.saturating_add(1),
+                ]);
             }
         }
 
-        None
+        for ip_value in ip_values {
+            let ip_address = Ipv4Addr::from(ip_value);
+            assert_eq!(
+                lpm.get_ipv4_match(&ip_address),
+                sorted.get_ipv4_match(&ip_address),
+                "{}",
+                ip_address
+            );
+        }
+
+        let single_block = HerrbischoffProvider::from_bytes(
+            &[("us", b"10.0.0.0/8\n".as_slice())],
+            &[],
+            &CountryFilter::default(),
+        )
+        .unwrap()
+        .with_lpm_index();
+        let outcome =
+            |ip_address: &str| single_block.get_ipv4_country(&ip_address.parse().unwrap());
+        assert_eq!(outcome("9.255.255.255"), LookupOutcome::BelowRange);
+        assert_eq!(outcome("11.0.0.0"), LookupOutcome::AboveRange);
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = HerrbischoffProvider::from_readers(
+            vec![(
+                "ipv4/us.cidr".to_owned(),
+                "# header\n10.0.0.0/16\n10.1.0.0/33\n".as_bytes(),
+            )],
+            vec![],
+            &CountryFilter::default(),
+            LineFormat::Cidr,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "failed to parse CIDR at ipv4/us.cidr:3: \"10.1.0.0/33\""
+        );
+        // The underlying parse error is kept as the cause
+        assert!(err.chain().count() > 1);
+    }
+
+    #[test]
+    fn test_from_archive() {
+        let dir = TempDir::new("ipgeod-archive-test");
+        let members = [
+            ("blocks-main/ipv4/us.cidr", "10.0.0.0/16\n"),
+            ("blocks-main/ipv6/de.cidr", "2001:db8::/32\n"),
+            ("blocks-main/README.md", "not a block list\n"),
+        ];
+
+        let tar_path = dir.path().join("blocks.tar.gz");
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            std::fs::File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        for (name, content) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let zip_path = dir.path().join("blocks.ZIP");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        for (name, content) in members {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut zip, content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        for path in [tar_path, zip_path] {
+            let provider = HerrbischoffProvider::from_repo(
+                &[path],
+                &CountryFilter::default(),
+                LineFormat::Cidr,
+            )
+            .unwrap();
+            assert_eq!(lookup(&provider, "10.0.0.1"), found("US", 16));
+            assert_eq!(
+                provider.get_ipv6_country(&"2001:db8::1".parse().unwrap()),
+                LookupOutcome::Found(CountryCode::new("DE").unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_mixed_line_format() {
+        let load = |files: &[(&str, &str)], countries: &CountryFilter, line_format| {
+            HerrbischoffProvider::from_readers(
+                files
+                    .iter()
+                    .map(|(file_name, content)| (file_name.to_string(), content.as_bytes()))
+                    .collect(),
+                vec![],
+                countries,
+                line_format,
+            )
+        };
+        let files = [
+            ("ipv4/all.cidr", "10.0.0.0/16,US\n10.1.0.0/16 , au\n"),
+            ("ipv4/de.cidr", "10.2.0.0/16\n10.3.0.0/16,FR\n"),
+        ];
+
+        let provider = load(&files, &CountryFilter::default(), LineFormat::Mixed).unwrap();
+        assert_eq!(lookup(&provider, "10.0.0.1"), found("US", 16));
+        assert_eq!(lookup(&provider, "10.1.0.1"), found("AU", 16));
+        assert_eq!(lookup(&provider, "10.2.0.1"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.3.0.1"), found("FR", 16));
+
+        // Countries are filtered line by line
+        let countries = CountryFilter {
+            include: vec![CountryCode::new("FR").unwrap()],
+            exclude: vec![],
+        };
+        let provider = load(&files, &countries, LineFormat::Mixed).unwrap();
+        assert_eq!(lookup(&provider, "10.2.0.1"), None);
+        assert_eq!(lookup(&provider, "10.3.0.1"), found("FR", 16));
+
+        // Commas are not special by default
+        assert!(load(&files, &CountryFilter::default(), LineFormat::Cidr).is_err());
+
+        for (name, files) in [
+            ("missing country", [("ipv4/all.cidr", "10.0.0.0/16\n")]),
+            ("invalid country", [("ipv4/all.cidr", "10.0.0.0/16,USA\n")]),
+        ] {
+            assert!(
+                load(&files, &CountryFilter::default(), LineFormat::Mixed).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_block_of_same_country() {
+        let provider = HerrbischoffProvider::from_bytes(
+            &[
+                (
+                    "us",
+                    b"10.1.0.0/16\n10.0.0.0/8\n10.1.0.0/16\n10.2.3.0/24\n".as_slice(),
+                ),
+                ("de", b"10.2.0.0/16\n".as_slice()),
+            ],
+            &[],
+            &CountryFilter::default(),
+        )
+        .unwrap();
+
+        // `10.1.0.0/16` is redundant, while `10.2.3.0/24` overrides a block of another country
+        assert_eq!(lookup(&provider, "10.0.255.255"), found("US", 8));
+        assert_eq!(lookup(&provider, "10.1.0.0"), found("US", 8));
+        assert_eq!(lookup(&provider, "10.2.0.0"), found("DE", 16));
+        assert_eq!(lookup(&provider, "10.2.3.0"), found("US", 24));
+        assert_eq!(lookup(&provider, "10.3.0.0"), found("US", 8));
+
+        let minimal_provider = HerrbischoffProvider::from_bytes(
+            &[
+                ("us", b"10.0.0.0/8\n10.2.3.0/24\n".as_slice()),
+                ("de", b"10.2.0.0/16\n".as_slice()),
+            ],
+            &[],
+            &CountryFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            provider.ipv4_range_count(),
+            minimal_provider.ipv4_range_count()
+        );
+    }
+
+    #[test]
+    fn test_same_block_of_different_countries() {
+        let result = HerrbischoffProvider::from_bytes(
+            &[
+                ("us", b"10.0.0.0/8\n10.1.0.0/16\n".as_slice()),
+                ("de", b"10.1.0.0/16\n".as_slice()),
+            ],
+            &[],
+            &CountryFilter::default(),
+        );
+
+        assert!(result.is_err());
     }
 }
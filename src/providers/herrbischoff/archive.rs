@@ -0,0 +1,134 @@
+//! Reads `.cidr` files straight out of release archives of the repository, without extracting them
+//! to disk.
+
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    path::{Component, Path},
+};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+
+use super::is_cidr_file;
+
+/// Contents of a `.cidr` file, paired with its path within the archive joined onto the archive
+/// path, e.g. `/data/country-ip-blocks.tar.gz/country-ip-blocks-master/ipv4/us.cidr`.
+pub type ArchiveFile = (String, Cursor<Vec<u8>>);
+
+/// Whether the path looks like a `.tar.gz`, `.tgz`, `.tar` or `.zip` archive, in any letter case.
+pub fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+/// Reads the `.cidr` files in `ipv4` and `ipv6` directories of the archive, which may be nested
+/// under a top-level directory as in GitHub release tarballs.
+pub fn read_cidr_files(path: &Path) -> anyhow::Result<(Vec<ArchiveFile>, Vec<ArchiveFile>)> {
+    let mut ipv4_files = vec![];
+    let mut ipv6_files = vec![];
+    let add_member = |member: &Path, content: Vec<u8>| {
+        let files = match family_dir(member) {
+            Some("ipv4") => &mut ipv4_files,
+            _ => &mut ipv6_files,
+        };
+        files.push((
+            path.join(member).display().to_string(),
+            Cursor::new(content),
+        ));
+    };
+
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    );
+    match archive_kind(path) {
+        Some(ArchiveKind::TarGz) => read_tar(tar::Archive::new(GzDecoder::new(file)), add_member),
+        Some(ArchiveKind::Tar) => read_tar(tar::Archive::new(file), add_member),
+        Some(ArchiveKind::Zip) => read_zip(file, add_member),
+        None => anyhow::bail!("unsupported archive format: {}", path.display()),
+    }
+    .with_context(|| format!("failed to read archive {}", path.display()))?;
+
+    Ok((ipv4_files, ipv6_files))
+}
+
+enum ArchiveKind {
+    TarGz,
+    Tar,
+    Zip,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let file_name = path.file_name()?.to_str()?.to_ascii_lowercase();
+
+    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if file_name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if file_name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else {
+        None
+    }
+}
+
+/// Whether the member is a `.cidr` file directly inside an `ipv4` or `ipv6` directory.
+fn is_wanted(member: &Path) -> bool {
+    matches!(family_dir(member), Some("ipv4" | "ipv6")) && is_cidr_file(member)
+}
+
+/// Name of the directory directly containing the member.
+fn family_dir(member: &Path) -> Option<&str> {
+    match member.parent()?.components().next_back()? {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    }
+}
+
+fn read_tar<R, F>(mut archive: tar::Archive<R>, mut add_member: F) -> anyhow::Result<()>
+where
+    R: Read,
+    F: FnMut(&Path, Vec<u8>),
+{
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let member = entry.path()?.into_owned();
+        if is_wanted(&member) {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            add_member(&member, content);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_zip<R, F>(reader: R, mut add_member: F) -> anyhow::Result<()>
+where
+    R: Read + std::io::Seek,
+    F: FnMut(&Path, Vec<u8>),
+{
+    let mut archive = zip::ZipArchive::new(reader)?;
+
+    for ind in 0..archive.len() {
+        let mut entry = archive.by_index(ind)?;
+        if !entry.is_file() {
+            continue;
+        }
+        // Members with unsafe paths, e.g. containing `..`, are skipped
+        let Some(member) = entry.enclosed_name() else {
+            continue;
+        };
+
+        if is_wanted(&member) {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            add_member(&member, content);
+        }
+    }
+
+    Ok(())
+}
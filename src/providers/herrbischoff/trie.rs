@@ -0,0 +1,110 @@
+//! Multibit prefix trie over IPv4 blocks, as an alternative to binary searching the sorted blocks.
+//!
+//! Addresses are consumed in strides of 16, 8 and 8 bits, so that any lookup takes at most three
+//! table reads. Blocks with a prefix length in between strides are expanded into all the slots
+//! they cover, which is cheap as the blocks are disjoint.
+
+/// Number of address bits consumed by each level, from the root down.
+const STRIDES: [u32; 3] = [16, 8, 8];
+
+/// Either empty, the index of the block covering the whole slot, or the offset of the next-level
+/// table splitting up the slot, which is marked with `TABLE_FLAG`. Packed into 4 bytes as tables
+/// make up most of the memory taken by the trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot(u32);
+
+const EMPTY: Slot = Slot(u32::MAX);
+const TABLE_FLAG: u32 = 1 << 31;
+
+impl Slot {
+    fn block(ind: usize) -> Self {
+        assert!(ind < (TABLE_FLAG - 1) as usize, "too many blocks for trie");
+        Self(ind as u32)
+    }
+
+    fn table(offset: usize) -> Self {
+        assert!(offset < (TABLE_FLAG - 1) as usize, "trie too large");
+        Self(offset as u32 | TABLE_FLAG)
+    }
+
+    fn table_offset(self) -> Option<usize> {
+        (self != EMPTY && self.0 & TABLE_FLAG != 0).then_some((self.0 & !TABLE_FLAG) as usize)
+    }
+}
+
+#[derive(Debug)]
+pub struct Ipv4Trie {
+    /// Tables of all levels laid out back to back, starting with the root table.
+    slots: Vec<Slot>,
+}
+
+impl Ipv4Trie {
+    /// Builds the trie from disjoint blocks given as `(start, prefix_len)`. Lookups return the
+    /// position of the block in the iterator.
+    pub fn new(blocks: impl Iterator<Item = (u32, u8)>) -> Self {
+        let mut trie = Self {
+            slots: vec![EMPTY; 1 << STRIDES[0]],
+        };
+
+        for (ind, (start, prefix_len)) in blocks.enumerate() {
+            trie.insert(start, u32::from(prefix_len), Slot::block(ind));
+        }
+
+        trie.slots.shrink_to_fit();
+        trie
+    }
+
+    /// Finds the block containing the address.
+    pub fn find(&self, value: u32) -> Option<usize> {
+        let mut offset = 0;
+        let mut consumed = 0;
+
+        for stride in STRIDES {
+            let slot = self.slots[offset + slot_index(value, consumed, stride)];
+            if slot == EMPTY {
+                return None;
+            }
+            match slot.table_offset() {
+                Some(table) => offset = table,
+                None => return Some(slot.0 as usize),
+            }
+            consumed += stride;
+        }
+
+        None
+    }
+
+    fn insert(&mut self, start: u32, prefix_len: u32, block: Slot) {
+        let mut offset = 0;
+        let mut consumed = 0;
+
+        for (level, stride) in STRIDES.into_iter().enumerate() {
+            let index = offset + slot_index(start, consumed, stride);
+
+            if prefix_len <= consumed + stride {
+                let count = 1 << (consumed + stride - prefix_len);
+                self.slots[index..index + count].fill(block);
+                return;
+            }
+
+            offset = match self.slots[index].table_offset() {
+                Some(table) => table,
+                // A block already in the slot is pushed down into the new table, although that
+                // doesn't happen with disjoint blocks
+                None => {
+                    let table = self.slots.len();
+                    self.slots
+                        .resize(table + (1 << STRIDES[level + 1]), self.slots[index]);
+                    self.slots[index] = Slot::table(table);
+                    table
+                }
+            };
+            consumed += stride;
+        }
+    }
+}
+
+/// Index into the table of the level that starts after `consumed` bits.
+fn slot_index(value: u32, consumed: u32, stride: u32) -> usize {
+    ((value << consumed) >> (32 - stride)) as usize
+}
@@ -1,97 +1,996 @@
 use std::{
-    io::{BufRead, BufReader},
-    net::Ipv4Addr,
+    collections::BTreeSet,
+    fmt::Display,
+    io::{BufRead, BufReader, Read},
+    net::{Ipv4Addr, Ipv6Addr},
 };
 
+use cidr::Ipv4Cidr;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use flate2::read::GzDecoder;
+use log::{debug, info, warn};
+use memmap2::Mmap;
+
+use super::{
+    find_range, AddressRange, CountryFilter, Ipv4Span, Location, LookupOutcome, GZIP_MAGIC,
+};
+use crate::{country::CountryCode, util::range_to_cidrs};
+
+mod index;
+
 #[derive(Debug)]
 pub struct Ip2locationProvider {
-    ip_ranges: Vec<IpRange>,
+    ipv4_ranges: Vec<IpRange<u32>>,
+    ipv6_ranges: Vec<IpRange<u128>>,
 }
 
 #[derive(Debug)]
-struct IpRange {
-    start: u32,
-    end: u32,
-    country: String,
+struct IpRange<T> {
+    start: T,
+    end: T,
+    country: CountryCode,
+    /// Only available from databases with location columns (e.g. DB11).
+    location: Option<Location>,
 }
 
-impl Ip2locationProvider {
-    pub fn from_db(db_path: &std::path::Path) -> anyhow::Result<Self> {
-        let mut ranges: Vec<IpRange> = vec![];
+impl<T: Ord + Copy> AddressRange for IpRange<T> {
+    type Value = T;
 
-        let mut file = std::fs::File::open(db_path)?;
-        let reader = BufReader::new(&mut file);
+    fn start(&self) -> T {
+        self.start
+    }
 
-        // TODO: use a proper CSV reader
-        for line in reader.lines() {
-            let line = line?;
+    fn end(&self) -> T {
+        self.end
+    }
+}
 
-            let cols = line
-                .split("\",\"")
-                .map(|col| col.trim_matches('"'))
-                .collect::<Vec<_>>();
+/// Options for loading databases. `mmap` and `sort` only apply to CSV-formatted databases.
+#[derive(Debug, Default, Clone)]
+pub struct Ip2locationOptions {
+    /// Memory-maps the database files instead of reading them through a buffer, leaving it to the
+    /// OS page cache to keep the raw file contents around.
+    pub mmap: bool,
+    /// Sorts rows by address after loading instead of requiring them to be sorted already.
+    /// Overlapping ranges are still rejected.
+    pub sort: bool,
+    /// Rows of other countries are dropped before validating the order of ranges.
+    pub countries: CountryFilter,
+    /// Placeholder code to keep rows without a country (`-`) under. Such rows are dropped if
+    /// unset.
+    pub unknown_country: Option<CountryCode>,
+}
 
-            if cols.len() < 3 {
-                anyhow::bail!("invalid row");
-            }
+/// Numeric representations of IP addresses.
+trait IpValue: Ord + Copy + std::ops::Sub<Output = Self> {
+    const ONE: Self;
+    const MAX: Self;
+}
 
-            let start: u32 = cols[0].parse()?;
-            let end: u32 = cols[1].parse()?;
-            let country_code = cols[2];
+impl Ip2locationProvider {
+    /// Loads the IPv4 and IPv6 databases. Either one can be omitted, in which case lookups for
+    /// that address family always miss.
+    ///
+    /// Consecutive rows of the same country (and location, if any) are merged into single ranges.
+    pub fn from_db(
+        ipv4_db_path: Option<&std::path::Path>,
+        ipv6_db_path: Option<&std::path::Path>,
+        options: &Ip2locationOptions,
+    ) -> anyhow::Result<Self> {
+        let ipv4_ranges = match ipv4_db_path {
+            Some(db_path) => load_ranges(db_path, options)?,
+            None => vec![],
+        };
+        let ipv6_ranges = match ipv6_db_path {
+            Some(db_path) => load_ranges(db_path, options)?,
+            None => vec![],
+        };
 
-            if country_code != "-" {
-                if country_code.len() != 2 {
-                    anyhow::bail!("invalid country code: {}", country_code);
-                }
+        Ok(Self::from_ranges(ipv4_ranges, ipv6_ranges))
+    }
 
-                // Makes sure that the list is sorted
-                if !ranges.is_empty() {
-                    let last_element = &ranges[ranges.len() - 1];
+    /// Same as [`Self::from_db`], but keeps the parsed ranges in a binary index file at
+    /// `index_path`. The index is used instead of the databases on subsequent loads, as long as
+    /// the databases (by path, size and modification time) and the relevant options are
+    /// unchanged. Otherwise the databases are parsed again and the index is rewritten.
+    ///
+    /// Failing to read or write the index is logged rather than treated as an error.
+    pub fn from_db_cached(
+        ipv4_db_path: Option<&std::path::Path>,
+        ipv6_db_path: Option<&std::path::Path>,
+        options: &Ip2locationOptions,
+        index_path: &std::path::Path,
+    ) -> anyhow::Result<Self> {
+        let fingerprint = index::fingerprint(ipv4_db_path, ipv6_db_path, options)?;
 
-                    if last_element.end >= start {
-                        anyhow::bail!("list not sorted");
-                    }
-                }
+        match index::read(index_path, &fingerprint) {
+            Ok(Some((ipv4_ranges, ipv6_ranges))) => {
+                info!(
+                    "Loaded IP2Location ranges from index {}",
+                    index_path.display()
+                );
 
-                ranges.push(IpRange {
-                    start,
-                    end,
-                    country: country_code.to_uppercase(),
+                // Ranges were already merged before being written
+                return Ok(Self {
+                    ipv4_ranges,
+                    ipv6_ranges,
                 });
             }
+            Ok(None) => {
+                info!(
+                    "IP2Location index {} missing or outdated",
+                    index_path.display()
+                );
+            }
+            Err(err) => {
+                warn!(
+                    "Unable to read IP2Location index {}: {}",
+                    index_path.display(),
+                    err
+                );
+            }
+        }
+
+        let provider = Self::from_db(ipv4_db_path, ipv6_db_path, options)?;
+
+        match index::write(
+            index_path,
+            &fingerprint,
+            &provider.ipv4_ranges,
+            &provider.ipv6_ranges,
+        ) {
+            Ok(()) => info!("Wrote IP2Location index {}", index_path.display()),
+            Err(err) => warn!(
+                "Unable to write IP2Location index {}: {}",
+                index_path.display(),
+                err
+            ),
         }
 
-        Ok(Self { ip_ranges: ranges })
+        Ok(provider)
     }
 
-    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> Option<String> {
+    /// Same as [`Self::from_db`], but reads the CSV databases from arbitrary sources such as
+    /// in-memory buffers. Gzip-compressed sources are detected by their leading bytes, and
+    /// `options.mmap` is ignored.
+    pub fn from_readers<R4, R6>(
+        ipv4_source: Option<R4>,
+        ipv6_source: Option<R6>,
+        options: &Ip2locationOptions,
+    ) -> anyhow::Result<Self>
+    where
+        R4: Read,
+        R6: Read,
+    {
+        let ipv4_ranges = match ipv4_source {
+            Some(source) => read_ranges(source, false, options)?,
+            None => vec![],
+        };
+        let ipv6_ranges = match ipv6_source {
+            Some(source) => read_ranges(source, false, options)?,
+            None => vec![],
+        };
+
+        Ok(Self::from_ranges(ipv4_ranges, ipv6_ranges))
+    }
+
+    fn from_ranges(
+        mut ipv4_ranges: Vec<IpRange<u32>>,
+        mut ipv6_ranges: Vec<IpRange<u128>>,
+    ) -> Self {
+        let merged_count =
+            merge_adjacent_ranges(&mut ipv4_ranges) + merge_adjacent_ranges(&mut ipv6_ranges);
+        if merged_count > 0 {
+            info!("Merged {} adjacent IP2Location ranges", merged_count);
+        }
+
+        // The parsed tables are kept for the lifetime of the provider, so it's worth giving back
+        // the excess capacity from growing the vectors.
+        ipv4_ranges.shrink_to_fit();
+        ipv6_ranges.shrink_to_fit();
+
+        Self {
+            ipv4_ranges,
+            ipv6_ranges,
+        }
+    }
+
+    /// Loads the IP2Location binary `.BIN` database, which may contain both IPv4 and IPv6 ranges.
+    pub fn from_bin(
+        bin_path: &std::path::Path,
+        options: &Ip2locationOptions,
+    ) -> anyhow::Result<Self> {
+        let data = std::fs::read(bin_path)?;
+
+        // Header layout:
+        //
+        // - byte 0: database type
+        // - byte 1: number of columns per record
+        // - bytes 2-4: release date
+        // - bytes 5-8: IPv4 record count
+        // - bytes 9-12: IPv4 records base address
+        // - bytes 13-16: IPv6 record count
+        // - bytes 17-20: IPv6 records base address
+        //
+        // All integers are little-endian, and all addresses are 1-based.
+        if data.len() < 21 {
+            anyhow::bail!("BIN file too short");
+        }
+        let column_count = data[1] as usize;
+        if column_count < 2 {
+            anyhow::bail!("invalid column count: {}", column_count);
+        }
+
+        let ipv4_count = read_bin_u32(&data, 5)? as usize;
+        let ipv4_base = read_bin_u32(&data, 9)? as usize;
+        let ipv6_count = read_bin_u32(&data, 13)? as usize;
+        let ipv6_base = read_bin_u32(&data, 17)? as usize;
+
+        // Each IPv4 record starts with a 4-byte `ip_from`, followed by 4-byte column values. IPv6
+        // records have a 16-byte `ip_from` instead. The country column always comes first.
+        let ipv4_ranges = load_bin_ranges(
+            &data,
+            ipv4_count,
+            ipv4_base,
+            column_count * 4,
+            4,
+            read_bin_u32,
+            options,
+        )?;
+        let ipv6_ranges = load_bin_ranges(
+            &data,
+            ipv6_count,
+            ipv6_base,
+            column_count * 4 + 12,
+            16,
+            read_bin_u128,
+            options,
+        )?;
+
+        Ok(Self {
+            ipv4_ranges,
+            ipv6_ranges,
+        })
+    }
+
+    pub fn ipv4_range_count(&self) -> usize {
+        self.ipv4_ranges.len()
+    }
+
+    pub fn ipv6_range_count(&self) -> usize {
+        self.ipv6_ranges.len()
+    }
+
+    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> LookupOutcome<CountryCode> {
+        find_range(&self.ipv4_ranges, u32::from_be_bytes(ip_address.octets()))
+            .map(|range| range.country)
+    }
+
+    pub fn get_ipv6_country(&self, ip_address: &Ipv6Addr) -> LookupOutcome<CountryCode> {
+        find_range(&self.ipv6_ranges, u128::from_be_bytes(ip_address.octets()))
+            .map(|range| range.country)
+    }
+
+    /// Finds the range containing the IPv4 address.
+    pub fn get_ipv4_range(&self, ip_address: &Ipv4Addr) -> LookupOutcome<Ipv4Span> {
+        find_range(&self.ipv4_ranges, u32::from_be_bytes(ip_address.octets())).map(|range| {
+            Ipv4Span {
+                start: range.start,
+                end: range.end,
+                country: range.country,
+            }
+        })
+    }
+
+    /// Finds the closest ranges starting at or below the IPv4 address and starting above it, for
+    /// telling which ranges surround an address that isn't covered.
+    pub fn get_ipv4_neighbors(
+        &self,
+        ip_address: &Ipv4Addr,
+    ) -> (Option<Ipv4Span>, Option<Ipv4Span>) {
         let ip_value = u32::from_be_bytes(ip_address.octets());
+        let ind = self
+            .ipv4_ranges
+            .partition_point(|range| range.start <= ip_value);
+
+        let span = |range: &IpRange<u32>| Ipv4Span {
+            start: range.start,
+            end: range.end,
+            country: range.country,
+        };
+
+        (
+            ind.checked_sub(1).map(|ind| span(&self.ipv4_ranges[ind])),
+            self.ipv4_ranges.get(ind).map(span),
+        )
+    }
+
+    /// Lists the distinct countries with any IPv4 or IPv6 ranges, sorted by code.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        let countries = self
+            .ipv4_ranges
+            .iter()
+            .map(|range| range.country)
+            .chain(self.ipv6_ranges.iter().map(|range| range.country))
+            .collect::<BTreeSet<_>>();
+
+        countries.into_iter().collect()
+    }
+
+    /// Lists the IPv4 ranges assigned to the country as a minimal set of CIDR blocks.
+    pub fn get_ipv4_cidrs(&self, country: CountryCode) -> Vec<Ipv4Cidr> {
+        self.ipv4_ranges
+            .iter()
+            .filter(|range| range.country == country)
+            .flat_map(|range| range_to_cidrs(range.start, range.end))
+            .collect()
+    }
+
+    pub fn get_ipv4_spans(&self, start: u32, end: u32) -> Vec<Ipv4Span> {
+        // Ranges are disjoint and sorted, so their `end` values are sorted too
+        let first = self.ipv4_ranges.partition_point(|range| range.end < start);
+
+        self.ipv4_ranges[first..]
+            .iter()
+            .take_while(|range| range.start <= end)
+            .map(|range| Ipv4Span {
+                start: range.start.max(start),
+                end: range.end.min(end),
+                country: range.country,
+            })
+            .collect()
+    }
+
+    pub fn get_ipv4_location(&self, ip_address: &Ipv4Addr) -> Option<Location> {
+        find_range(&self.ipv4_ranges, u32::from_be_bytes(ip_address.octets()))
+            .found()
+            .and_then(|range| range.location.clone())
+    }
+
+    pub fn get_ipv6_location(&self, ip_address: &Ipv6Addr) -> Option<Location> {
+        find_range(&self.ipv6_ranges, u128::from_be_bytes(ip_address.octets()))
+            .found()
+            .and_then(|range| range.location.clone())
+    }
+}
+
+fn load_ranges<T>(
+    db_path: &std::path::Path,
+    options: &Ip2locationOptions,
+) -> anyhow::Result<Vec<IpRange<T>>>
+where
+    T: TryFrom<u128> + Ord + Display,
+{
+    let file = std::fs::File::open(db_path)?;
+    let has_gz_extension = db_path.extension().is_some_and(|value| value == "gz");
+
+    if options.mmap {
+        // SAFETY: the mapping only lives until parsing finishes, and files replaced by renaming
+        // keep the old contents mapped. Truncating the file in place while it's being loaded
+        // would crash the process with `SIGBUS`, which is why `--mmap` rejects `--watch` and
+        // documents that databases must be replaced atomically.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if has_gz_extension || mmap.starts_with(&GZIP_MAGIC) {
+            parse_ranges(GzDecoder::new(&mmap[..]), options)
+        } else {
+            parse_ranges(&mmap[..], options)
+        }
+    } else {
+        read_ranges(file, has_gz_extension, options)
+    }
+}
+
+/// Parses a CSV database from `source`, decompressing it if `gzip` is set or it starts with the
+/// gzip magic bytes.
+fn read_ranges<T, R>(
+    source: R,
+    gzip: bool,
+    options: &Ip2locationOptions,
+) -> anyhow::Result<Vec<IpRange<T>>>
+where
+    T: TryFrom<u128> + Ord + Display,
+    R: Read,
+{
+    let mut reader = BufReader::new(source);
+
+    if gzip || reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        parse_ranges(GzDecoder::new(reader), options)
+    } else {
+        parse_ranges(reader, options)
+    }
+}
+
+fn parse_ranges<T, R>(source: R, options: &Ip2locationOptions) -> anyhow::Result<Vec<IpRange<T>>>
+where
+    T: TryFrom<u128> + Ord + Display,
+    R: Read,
+{
+    let mut ranges: Vec<IpRange<T>> = vec![];
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(source);
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        if record.len() < 3 {
+            anyhow::bail!("invalid row");
+        }
+
+        // Combined databases can carry IPv6 rows that don't fit into an IPv4 table. They're skipped
+        // instead of failing the whole load.
+        let (start, end) = match (
+            T::try_from(record[0].parse::<u128>()?),
+            T::try_from(record[1].parse::<u128>()?),
+        ) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => {
+                debug!(
+                    "Skipping out-of-range IP2Location row: {}-{}",
+                    &record[0], &record[1]
+                );
+                continue;
+            }
+        };
+        if start > end {
+            anyhow::bail!("range starts after it ends: {}-{}", start, end);
+        }
+        let country_code = &record[2];
+
+        let Some(country) = resolve_country(country_code, options)? else {
+            continue;
+        };
+
+        // Makes sure that the list is sorted, unless it's going to be sorted afterwards
+        if !options.sort && !ranges.is_empty() {
+            let last_element = &ranges[ranges.len() - 1];
+
+            if last_element.end >= start {
+                anyhow::bail!("list not sorted");
+            }
+        }
+
+        // DB11 and above come with region, city, latitude, and longitude columns after the
+        // country name. The smaller DB1 schema stops at the country name.
+        let location = if record.len() >= 8 {
+            Some(Location {
+                region: parse_text_column(&record[4]),
+                city: parse_text_column(&record[5]),
+                latitude: record[6].parse()?,
+                longitude: record[7].parse()?,
+            })
+        } else {
+            None
+        };
+
+        ranges.push(IpRange {
+            start,
+            end,
+            country,
+            location,
+        });
+    }
+
+    if options.sort {
+        ranges.sort_unstable_by(|a, b| a.start.cmp(&b.start));
+
+        for pair in ranges.windows(2) {
+            if pair[0].end >= pair[1].start {
+                anyhow::bail!(
+                    "overlapping ranges: {}-{} ({}) and {}-{} ({})",
+                    pair[0].start,
+                    pair[0].end,
+                    pair[0].country,
+                    pair[1].start,
+                    pair[1].end,
+                    pair[1].country
+                );
+            }
+        }
+    }
 
-        match self
-            .ip_ranges
-            .binary_search_by_key(&ip_value, |item| item.start)
+    Ok(ranges)
+}
+
+impl IpValue for u32 {
+    const ONE: Self = 1;
+    const MAX: Self = u32::MAX;
+}
+
+impl IpValue for u128 {
+    const ONE: Self = 1;
+    const MAX: Self = u128::MAX;
+}
+
+/// Merges each range into the previous one when they're contiguous and carry identical data,
+/// returning the number of ranges merged away. Expects the ranges to be sorted and disjoint.
+fn merge_adjacent_ranges<T>(ranges: &mut Vec<IpRange<T>>) -> usize
+where
+    T: IpValue,
+{
+    let original_len = ranges.len();
+
+    ranges.dedup_by(|next, prev| {
+        // Disjoint sorting guarantees `next.start > prev.end`, so this can't underflow, and there's
+        // no `prev.end + 1` to overflow at the end of the address space.
+        if next.start - T::ONE == prev.end
+            && next.country == prev.country
+            && next.location == prev.location
         {
-            Ok(ind) => {
-                // `start` matches perfectly with `ip_value`
-                let range = &self.ip_ranges[ind];
-                Some(range.country.to_owned())
+            prev.end = next.end;
+            true
+        } else {
+            false
+        }
+    });
+    ranges.shrink_to_fit();
+
+    original_len - ranges.len()
+}
+
+/// Reads `count` consecutive records from a BIN file. The `end` of each range is derived from the
+/// `ip_from` of the next record, as the record table is terminated by an extra sentinel record.
+fn load_bin_ranges<T>(
+    data: &[u8],
+    count: usize,
+    base_address: usize,
+    record_size: usize,
+    ip_size: usize,
+    read_ip: fn(&[u8], usize) -> anyhow::Result<T>,
+    options: &Ip2locationOptions,
+) -> anyhow::Result<Vec<IpRange<T>>>
+where
+    T: IpValue,
+{
+    let mut ranges: Vec<IpRange<T>> = vec![];
+    if count == 0 {
+        return Ok(ranges);
+    }
+
+    let base_offset = base_address
+        .checked_sub(1)
+        .ok_or_else(|| anyhow::anyhow!("invalid base address"))?;
+
+    for ind in 0..count {
+        let offset = base_offset + ind * record_size;
+
+        let start = read_ip(data, offset)?;
+        let next_start = read_ip(data, offset + record_size)?;
+        let end = if next_start > start {
+            next_start - T::ONE
+        } else {
+            T::MAX
+        };
+
+        let country_pointer = read_bin_u32(data, offset + ip_size)? as usize;
+        let country_code = read_bin_str(data, country_pointer)?;
+
+        let Some(country) = resolve_country(country_code, options)? else {
+            continue;
+        };
+
+        // Makes sure that the list is sorted
+        if let Some(last_element) = ranges.last() {
+            if last_element.end >= start {
+                anyhow::bail!("list not sorted");
             }
-            Err(ind) => {
-                if ind > 0 {
-                    // No exact `start` matches. This is the closest range
-                    let range = &self.ip_ranges[ind - 1];
-
-                    if range.end >= ip_value {
-                        // The closest range includes `ip_value`
-                        Some(range.country.to_owned())
-                    } else {
-                        // `ip_value` falls in the gap between two ranges
-                        None
+        }
+
+        ranges.push(IpRange {
+            start,
+            end,
+            country,
+            location: None,
+        });
+    }
+
+    Ok(ranges)
+}
+
+fn read_bin_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of BIN file"))?;
+
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_bin_u128(data: &[u8], offset: usize) -> anyhow::Result<u128> {
+    let bytes = data
+        .get(offset..offset + 16)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of BIN file"))?;
+
+    Ok(u128::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a length-prefixed string.
+fn read_bin_str(data: &[u8], offset: usize) -> anyhow::Result<&str> {
+    let len = *data
+        .get(offset)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of BIN file"))? as usize;
+    let bytes = data
+        .get(offset + 1..offset + 1 + len)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of BIN file"))?;
+
+    Ok(std::str::from_utf8(bytes)?)
+}
+
+/// Resolves the country column, returning `None` for rows that should be dropped.
+fn resolve_country(
+    country_code: &str,
+    options: &Ip2locationOptions,
+) -> anyhow::Result<Option<CountryCode>> {
+    let country = if country_code == "-" {
+        match options.unknown_country {
+            Some(unknown_country) => unknown_country,
+            None => return Ok(None),
+        }
+    } else {
+        CountryCode::new(country_code)
+            .ok_or_else(|| anyhow::anyhow!("invalid country code: {}", country_code))?
+    };
+
+    Ok(Some(country).filter(|country| options.countries.allows(*country)))
+}
+
+/// Text columns use `-` for missing values.
+fn parse_text_column(value: &str) -> Option<String> {
+    if value.is_empty() || value == "-" {
+        None
+    } else {
+        Some(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    /// Countries expected at the given addresses, or `None` if loading is expected to fail.
+    type ExpectedCountries = Option<&'static [(&'static str, &'static str)]>;
+
+    fn provider(ranges: &[(&str, &str, &str)]) -> Ip2locationProvider {
+        Ip2locationProvider {
+            ipv4_ranges: ranges
+                .iter()
+                .map(|(start, end, country)| IpRange {
+                    start: addr(start),
+                    end: addr(end),
+                    country: CountryCode::new(country).unwrap(),
+                    location: None,
+                })
+                .collect(),
+            ipv6_ranges: vec![],
+        }
+    }
+
+    fn addr(value: &str) -> u32 {
+        u32::from(value.parse::<Ipv4Addr>().unwrap())
+    }
+
+    fn lookup(provider: &Ip2locationProvider, ip_address: &str) -> LookupOutcome<CountryCode> {
+        provider.get_ipv4_country(&ip_address.parse().unwrap())
+    }
+
+    fn found(country: &str) -> LookupOutcome<CountryCode> {
+        LookupOutcome::Found(CountryCode::new(country).unwrap())
+    }
+
+    fn from_csv(csv: &str, options: &Ip2locationOptions) -> anyhow::Result<Ip2locationProvider> {
+        Ip2locationProvider::from_readers(Some(csv.as_bytes()), None::<&[u8]>, options)
+    }
+
+    #[test]
+    fn test_parse_rows() {
+        let sorted = Ip2locationOptions {
+            sort: true,
+            ..Default::default()
+        };
+        let unknown = Ip2locationOptions {
+            unknown_country: CountryCode::new("ZZ"),
+            ..Default::default()
+        };
+
+        let cases: [(&str, &str, &Ip2locationOptions, ExpectedCountries); 11] = [
+            (
+                "sorted rows",
+                "\"16777216\",\"16777471\",\"US\",\"United States of America\"\n\
+                 \"16777472\",\"16777727\",\"AU\",\"Australia\"\n",
+                &Ip2locationOptions::default(),
+                Some(&[("1.0.0.0", "US"), ("1.0.1.0", "AU")]),
+            ),
+            (
+                "unsorted rows",
+                "16777472,16777727,AU,Australia\n16777216,16777471,US,United States\n",
+                &Ip2locationOptions::default(),
+                None,
+            ),
+            (
+                "unsorted rows with sorting",
+                "16777472,16777727,AU,Australia\n16777216,16777471,US,United States\n",
+                &sorted,
+                Some(&[("1.0.0.0", "US"), ("1.0.1.0", "AU")]),
+            ),
+            (
+                "overlapping rows with sorting",
+                "16777216,16777727,AU,Australia\n16777216,16777471,US,United States\n",
+                &sorted,
+                None,
+            ),
+            (
+                "unknown country dropped",
+                "16777216,16777471,-,-\n16777472,16777727,AU,Australia\n",
+                &Ip2locationOptions::default(),
+                Some(&[("1.0.1.0", "AU")]),
+            ),
+            (
+                "unknown country kept",
+                "16777216,16777471,-,-\n16777472,16777727,AU,Australia\n",
+                &unknown,
+                Some(&[("1.0.0.0", "ZZ"), ("1.0.1.0", "AU")]),
+            ),
+            (
+                "too few columns",
+                "16777216,16777471\n",
+                &Ip2locationOptions::default(),
+                None,
+            ),
+            (
+                "non-numeric address",
+                "1.0.0.0,16777471,US,United States\n",
+                &Ip2locationOptions::default(),
+                None,
+            ),
+            (
+                "invalid country code",
+                "16777216,16777471,USA,United States\n",
+                &Ip2locationOptions::default(),
+                None,
+            ),
+            (
+                "range starting after its end",
+                "16777471,16777216,US,United States\n",
+                &Ip2locationOptions::default(),
+                None,
+            ),
+            (
+                "IPv6 row in IPv4 table",
+                "16777216,16777471,US,United States\n\
+                 281470681743360,281474976710655,AU,Australia\n",
+                &Ip2locationOptions::default(),
+                Some(&[("1.0.0.0", "US")]),
+            ),
+        ];
+        for (name, csv, options, expected) in cases {
+            let result = from_csv(csv, options);
+            match expected {
+                Some(expected) => {
+                    let provider = result.unwrap_or_else(|err| panic!("{}: {}", name, err));
+                    for (ip_address, country) in expected {
+                        assert_eq!(lookup(&provider, ip_address), found(country), "{}", name);
                     }
-                } else {
-                    // `ip_value` is smaller even than the first record
-                    None
                 }
+                None => assert!(result.is_err(), "{}", name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_gzip_detection() {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"16777216,16777471,US,United States\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let provider = Ip2locationProvider::from_readers(
+            Some(compressed.as_slice()),
+            None::<&[u8]>,
+            &Ip2locationOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(lookup(&provider, "1.0.0.0"), found("US"));
+    }
+
+    #[test]
+    fn test_range_boundaries() {
+        // Two adjacent ranges followed by a gap and a single-address range
+        let provider = provider(&[
+            ("1.0.0.0", "1.0.0.255", "US"),
+            ("1.0.1.0", "1.0.1.255", "AU"),
+            ("1.0.3.0", "1.0.3.0", "JP"),
+        ]);
+
+        let cases = [
+            ("0.255.255.255", LookupOutcome::BelowRange),
+            ("1.0.0.0", found("US")),
+            ("1.0.0.1", found("US")),
+            ("1.0.0.255", found("US")),
+            ("1.0.1.0", found("AU")),
+            ("1.0.1.255", found("AU")),
+            ("1.0.2.0", LookupOutcome::InGap),
+            ("1.0.2.255", LookupOutcome::InGap),
+            ("1.0.3.0", found("JP")),
+            ("1.0.3.1", LookupOutcome::AboveRange),
+            ("255.255.255.255", LookupOutcome::AboveRange),
+        ];
+        for (ip_address, expected) in cases {
+            assert_eq!(lookup(&provider, ip_address), expected, "{}", ip_address);
+        }
+    }
+
+    #[test]
+    fn test_address_space_boundaries() {
+        let provider = provider(&[
+            ("0.0.0.0", "0.0.0.255", "US"),
+            ("255.255.255.0", "255.255.255.255", "AU"),
+        ]);
+
+        let cases = [
+            ("0.0.0.0", found("US")),
+            ("0.0.0.255", found("US")),
+            ("0.0.1.0", LookupOutcome::InGap),
+            ("255.255.254.255", LookupOutcome::InGap),
+            ("255.255.255.0", found("AU")),
+            ("255.255.255.255", found("AU")),
+        ];
+        for (ip_address, expected) in cases {
+            assert_eq!(lookup(&provider, ip_address), expected, "{}", ip_address);
+        }
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let provider = provider(&[
+            ("1.0.0.0", "1.0.0.255", "US"),
+            ("1.0.2.0", "1.0.2.255", "AU"),
+        ]);
+        let neighbors = |ip_address: &str| {
+            let (below, above) = provider.get_ipv4_neighbors(&ip_address.parse().unwrap());
+            (
+                below.map(|span| span.country.to_string()),
+                above.map(|span| span.country.to_string()),
+            )
+        };
+        let country = |value: &str| Some(value.to_owned());
+
+        assert_eq!(neighbors("0.255.255.255"), (None, country("US")));
+        assert_eq!(neighbors("1.0.1.0"), (country("US"), country("AU")));
+        assert_eq!(neighbors("1.0.1.255"), (country("US"), country("AU")));
+        assert_eq!(neighbors("1.0.3.0"), (country("AU"), None));
+    }
+
+    #[test]
+    fn test_full_address_space() {
+        let provider = provider(&[("0.0.0.0", "255.255.255.255", "US")]);
+
+        for ip_address in ["0.0.0.0", "128.0.0.0", "255.255.255.255"] {
+            assert_eq!(lookup(&provider, ip_address), found("US"), "{}", ip_address);
+        }
+    }
+
+    #[test]
+    fn test_no_ranges() {
+        let provider = provider(&[]);
+
+        for ip_address in ["0.0.0.0", "255.255.255.255"] {
+            assert_eq!(
+                lookup(&provider, ip_address),
+                LookupOutcome::BelowRange,
+                "{}",
+                ip_address
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_cache() {
+        let dir = TempDir::new("ipgeod-index-test");
+        let db_path = dir.path().join("IP2LOCATION-LITE-DB1.CSV");
+        let index_path = dir.path().join("index.bin");
+        let options = Ip2locationOptions::default();
+        let load = || {
+            Ip2locationProvider::from_db_cached(Some(&db_path), None, &options, &index_path)
+                .unwrap()
+        };
+
+        std::fs::write(&db_path, "16777216,16777471,US,United States\n").unwrap();
+        assert_eq!(lookup(&load(), "1.0.0.0"), found("US"));
+        assert!(index_path.exists());
+
+        // Served from the index even though the database no longer parses
+        let modified = std::fs::metadata(&db_path).unwrap().modified().unwrap();
+        std::fs::write(&db_path, "16777216,16777471,United States,XX\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&db_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(lookup(&load(), "1.0.0.0"), found("US"));
+
+        // Changed databases are parsed again
+        std::fs::write(&db_path, "16777216,16777471,AU,Australia\n").unwrap();
+        assert_eq!(lookup(&load(), "1.0.0.0"), found("AU"));
+        assert_eq!(lookup(&load(), "1.0.0.0"), found("AU"));
+
+        // Locations and IPv6 ranges survive the round trip
+        let ipv4_ranges = vec![IpRange {
+            start: addr("1.0.0.0"),
+            end: addr("1.0.0.255"),
+            country: CountryCode::new("US").unwrap(),
+            location: Some(Location {
+                region: Some("California".to_owned()),
+                city: None,
+                latitude: 34.05,
+                longitude: -118.24,
+            }),
+        }];
+        let ipv6_ranges = vec![IpRange {
+            start: 0,
+            end: u128::MAX,
+            country: CountryCode::new("AU").unwrap(),
+            location: None,
+        }];
+        index::write(&index_path, &[1; 32], &ipv4_ranges, &ipv6_ranges).unwrap();
+
+        assert!(index::read(&index_path, &[2; 32]).unwrap().is_none());
+        let (read_ipv4_ranges, read_ipv6_ranges) =
+            index::read(&index_path, &[1; 32]).unwrap().unwrap();
+        assert_eq!(
+            format!("{:?}", (&read_ipv4_ranges, &read_ipv6_ranges)),
+            format!("{:?}", (&ipv4_ranges, &ipv6_ranges))
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_find_range_matches_linear_scan(
+            base in proptest::prop_oneof![
+                proptest::strategy::Just(0u32),
+                proptest::strategy::Just(u32::MAX - 4096),
+                0..u32::MAX - 4096,
+            ],
+            blocks in proptest::collection::vec((0u32..64, 0u32..64), 0..32),
+            offsets in proptest::collection::vec(0u32..4096, 1..64),
+        ) {
+            // At most 32 blocks of under 128 addresses each, so nothing overflows past `base + 4096`
+            let mut ranges = vec![];
+            let mut next = base;
+            for (gap, len) in blocks {
+                let start = next + gap;
+                ranges.push(IpRange {
+                    start,
+                    end: start + len,
+                    country: CountryCode::new("US").unwrap(),
+                    location: None,
+                });
+                next = start + len + 1;
+            }
+
+            for offset in offsets {
+                let ip_value = base + offset;
+                let expected = match ranges
+                    .iter()
+                    .find(|range| range.start <= ip_value && ip_value <= range.end)
+                {
+                    Some(range) => LookupOutcome::Found((range.start, range.end)),
+                    None if ranges.first().is_none_or(|range| ip_value < range.start) => {
+                        LookupOutcome::BelowRange
+                    }
+                    None if ranges.last().is_some_and(|range| ip_value > range.end) => {
+                        LookupOutcome::AboveRange
+                    }
+                    None => LookupOutcome::InGap,
+                };
+
+                proptest::prop_assert_eq!(
+                    find_range(&ranges, ip_value).map(|range| (range.start, range.end)),
+                    expected
+                );
             }
         }
     }
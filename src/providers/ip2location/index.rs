@@ -0,0 +1,255 @@
+//! Binary index files holding parsed ranges, so that CSV databases don't need to be parsed again
+//! as long as they're unchanged.
+//!
+//! Layout, with all integers in little-endian:
+//!
+//! - 8 bytes: [`INDEX_MAGIC`]
+//! - 4 bytes: [`INDEX_VERSION`]
+//! - 32 bytes: fingerprint of the source databases and load options
+//! - IPv4 ranges, then IPv6 ranges, each as an 8-byte count followed by the ranges
+//!
+//! Each range consists of its `start` and `end` (4 bytes each for IPv4, 16 bytes each for IPv6),
+//! the 2-byte country code, and a location flag byte. Ranges with the flag set are followed by
+//! the region and the city as strings, and the latitude and the longitude as 8-byte floats.
+//! Strings are prefixed by a 4-byte length, where `u32::MAX` stands for a missing value.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{Ip2locationOptions, IpRange};
+use crate::{country::CountryCode, providers::Location};
+
+const INDEX_MAGIC: &[u8; 8] = b"IPGEOIDX";
+/// Bumped on any change to the layout, which invalidates existing index files.
+const INDEX_VERSION: u32 = 1;
+
+/// Identifies the source databases, by path, size and modification time, along with the options
+/// that affect parsing.
+pub type Fingerprint = [u8; 32];
+
+pub fn fingerprint(
+    ipv4_db_path: Option<&Path>,
+    ipv6_db_path: Option<&Path>,
+    options: &Ip2locationOptions,
+) -> anyhow::Result<Fingerprint> {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+
+    for db_path in [ipv4_db_path, ipv6_db_path] {
+        match db_path {
+            Some(db_path) => {
+                let metadata = std::fs::metadata(db_path)?;
+                let modified = metadata
+                    .modified()?
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default();
+
+                hasher.update(db_path.canonicalize()?.display().to_string());
+                hasher.update(metadata.len().to_le_bytes());
+                hasher.update(modified.as_nanos().to_le_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+
+    // `mmap` only affects how the files are read
+    hasher.update(format!(
+        "{} {:?} {:?}",
+        options.sort, options.countries, options.unknown_country
+    ));
+
+    Ok(hasher.finalize().into())
+}
+
+/// Reads the ranges from the index file, returning `None` if it doesn't exist or was written for
+/// a different fingerprint.
+#[allow(clippy::type_complexity)]
+pub fn read(
+    index_path: &Path,
+    fingerprint: &Fingerprint,
+) -> anyhow::Result<Option<(Vec<IpRange<u32>>, Vec<IpRange<u128>>)>> {
+    let data = match std::fs::read(index_path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut reader = IndexReader { data: &data };
+    if reader.take(INDEX_MAGIC.len())? != INDEX_MAGIC {
+        anyhow::bail!("not an index file");
+    }
+    if u32::read(&mut reader)? != INDEX_VERSION || reader.take(fingerprint.len())? != fingerprint {
+        return Ok(None);
+    }
+
+    let ipv4_ranges = read_ranges(&mut reader)?;
+    let ipv6_ranges = read_ranges(&mut reader)?;
+    if !reader.data.is_empty() {
+        anyhow::bail!("trailing data in index file");
+    }
+
+    Ok(Some((ipv4_ranges, ipv6_ranges)))
+}
+
+/// Writes the ranges to the index file. The file is replaced atomically, so that concurrent
+/// readers never see a partially written index.
+pub fn write(
+    index_path: &Path,
+    fingerprint: &Fingerprint,
+    ipv4_ranges: &[IpRange<u32>],
+    ipv6_ranges: &[IpRange<u128>],
+) -> anyhow::Result<()> {
+    let temp_path = index_path.with_extension("tmp");
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    writer.write_all(INDEX_MAGIC)?;
+    writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+    writer.write_all(fingerprint)?;
+    write_ranges(&mut writer, ipv4_ranges)?;
+    write_ranges(&mut writer, ipv6_ranges)?;
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+
+    std::fs::rename(&temp_path, index_path)?;
+
+    Ok(())
+}
+
+fn read_ranges<T>(reader: &mut IndexReader) -> anyhow::Result<Vec<IpRange<T>>>
+where
+    T: IndexValue,
+{
+    let count = u64::read(reader)? as usize;
+
+    // Each range takes at least 3 bytes besides the addresses, so a corrupted count can't cause a
+    // huge allocation
+    let mut ranges = Vec::with_capacity(count.min(reader.data.len() / 3));
+    for _ in 0..count {
+        let start = T::read(reader)?;
+        let end = T::read(reader)?;
+        let country = std::str::from_utf8(reader.take(2)?)
+            .ok()
+            .and_then(CountryCode::new)
+            .ok_or_else(|| anyhow::anyhow!("invalid country code in index file"))?;
+        let location = match reader.take(1)?[0] {
+            0 => None,
+            _ => Some(Location {
+                region: reader.read_str()?,
+                city: reader.read_str()?,
+                latitude: f64::from_bits(u64::read(reader)?),
+                longitude: f64::from_bits(u64::read(reader)?),
+            }),
+        };
+
+        ranges.push(IpRange {
+            start,
+            end,
+            country,
+            location,
+        });
+    }
+
+    Ok(ranges)
+}
+
+fn write_ranges<T, W>(writer: &mut W, ranges: &[IpRange<T>]) -> anyhow::Result<()>
+where
+    T: IndexValue,
+    W: Write,
+{
+    writer.write_all(&(ranges.len() as u64).to_le_bytes())?;
+
+    for range in ranges.iter() {
+        range.start.write(writer)?;
+        range.end.write(writer)?;
+        writer.write_all(range.country.as_str().as_bytes())?;
+        match &range.location {
+            Some(location) => {
+                writer.write_all(&[1])?;
+                write_str(writer, location.region.as_deref())?;
+                write_str(writer, location.city.as_deref())?;
+                writer.write_all(&location.latitude.to_bits().to_le_bytes())?;
+                writer.write_all(&location.longitude.to_bits().to_le_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+    }
+
+    Ok(())
+}
+
+fn write_str<W: Write>(writer: &mut W, value: Option<&str>) -> anyhow::Result<()> {
+    match value {
+        Some(value) => {
+            let len = u32::try_from(value.len())
+                .ok()
+                .filter(|len| *len != u32::MAX)
+                .ok_or_else(|| anyhow::anyhow!("string too long for index file"))?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(value.as_bytes())?;
+        }
+        None => writer.write_all(&u32::MAX.to_le_bytes())?,
+    }
+
+    Ok(())
+}
+
+struct IndexReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> IndexReader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.data.len() < len {
+            anyhow::bail!("unexpected end of index file");
+        }
+
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn read_str(&mut self) -> anyhow::Result<Option<String>> {
+        match u32::read(self)? {
+            u32::MAX => Ok(None),
+            len => Ok(Some(
+                std::str::from_utf8(self.take(len as usize)?)?.to_owned(),
+            )),
+        }
+    }
+}
+
+/// Integers stored in index files.
+trait IndexValue: Copy {
+    fn read(reader: &mut IndexReader) -> anyhow::Result<Self>;
+
+    fn write<W: Write>(self, writer: &mut W) -> anyhow::Result<()>;
+}
+
+macro_rules! impl_index_value {
+    ($($ty:ty),*) => {
+        $(
+            impl IndexValue for $ty {
+                fn read(reader: &mut IndexReader) -> anyhow::Result<Self> {
+                    let bytes = reader.take(std::mem::size_of::<Self>())?;
+                    Ok(Self::from_le_bytes(bytes.try_into().expect("length checked")))
+                }
+
+                fn write<W: Write>(self, writer: &mut W) -> anyhow::Result<()> {
+                    writer.write_all(&self.to_le_bytes())?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_index_value!(u32, u64, u128);
@@ -0,0 +1,377 @@
+use std::{
+    collections::BTreeSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use cidr::Ipv4Cidr;
+use ipnetwork::{IpNetwork, Ipv4Network};
+use maxminddb::{LookupResult, PathElement, Reader};
+
+use super::{CountryFilter, Ipv4Span, LookupOutcome};
+use crate::{country::CountryCode, util::range_to_cidrs};
+
+const COUNTRY_CODE_PATH: &[PathElement<'static>] =
+    &[PathElement::Key("country"), PathElement::Key("iso_code")];
+
+#[derive(Debug)]
+pub struct MaxmindProvider {
+    reader: Reader<Vec<u8>>,
+    /// The database is used as is, so networks of other countries are skipped while decoding.
+    countries: CountryFilter,
+    ipv4_network_count: usize,
+    ipv6_network_count: usize,
+    /// Collected along with the counts as the networks can only be enumerated by a full scan.
+    country_codes: Vec<CountryCode>,
+}
+
+impl MaxmindProvider {
+    /// Loads a MaxMind DB (`.mmdb`) file with country data, such as GeoLite2-Country.
+    pub fn from_mmdb(mmdb_path: &Path, countries: CountryFilter) -> anyhow::Result<Self> {
+        Self::from_reader(Reader::open_readfile(mmdb_path)?, countries)
+    }
+
+    fn from_reader(reader: Reader<Vec<u8>>, countries: CountryFilter) -> anyhow::Result<Self> {
+        let mut provider = Self {
+            reader,
+            countries,
+            ipv4_network_count: 0,
+            ipv6_network_count: 0,
+            country_codes: vec![],
+        };
+
+        // The search tree doesn't record how many networks it holds, so they're counted once here
+        // for health reporting
+        let mut ipv4_network_count = 0;
+        let mut ipv6_network_count = 0;
+        let mut country_codes = BTreeSet::new();
+        for item in provider
+            .reader
+            .within(IpNetwork::V4("0.0.0.0/0".parse()?), Default::default())?
+        {
+            if let Some(country) = provider.decode_country(&item?) {
+                ipv4_network_count += 1;
+                country_codes.insert(country);
+            }
+        }
+        if provider.reader.metadata().ip_version == 6 {
+            for item in provider
+                .reader
+                .within(IpNetwork::V6("::/0".parse()?), Default::default())?
+            {
+                let item = item?;
+                if let IpNetwork::V6(_) = item.network()? {
+                    if let Some(country) = provider.decode_country(&item) {
+                        ipv6_network_count += 1;
+                        country_codes.insert(country);
+                    }
+                }
+            }
+        }
+
+        provider.ipv4_network_count = ipv4_network_count;
+        provider.ipv6_network_count = ipv6_network_count;
+        provider.country_codes = country_codes.into_iter().collect();
+
+        Ok(provider)
+    }
+
+    pub fn ipv4_range_count(&self) -> usize {
+        self.ipv4_network_count
+    }
+
+    pub fn ipv6_range_count(&self) -> usize {
+        self.ipv6_network_count
+    }
+
+    /// Lists the distinct countries with any IPv4 or IPv6 networks, sorted by code.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        self.country_codes.clone()
+    }
+
+    /// Lists the IPv4 networks assigned to the country.
+    pub fn get_ipv4_cidrs(&self, country: CountryCode) -> Vec<Ipv4Cidr> {
+        self.ipv4_networks(IpNetwork::V4("0.0.0.0/0".parse().expect("valid network")))
+            .filter(|(_, network_country)| *network_country == country)
+            .filter_map(|(network, _)| Ipv4Cidr::new(network.network(), network.prefix()).ok())
+            .collect()
+    }
+
+    pub fn get_ipv4_spans(&self, start: u32, end: u32) -> Vec<Ipv4Span> {
+        let mut spans: Vec<Ipv4Span> = vec![];
+        for cidr in range_to_cidrs(start, end) {
+            let Ok(block) = Ipv4Network::new(cidr.first_address(), cidr.network_length()) else {
+                continue;
+            };
+
+            for (network, country) in self.ipv4_networks(IpNetwork::V4(block)) {
+                // A network found while searching a smaller block can extend past it, in which
+                // case it's found again under the neighbouring blocks
+                let span = Ipv4Span {
+                    start: u32::from(network.network()).max(u32::from(cidr.first_address())),
+                    end: u32::from(network.broadcast()).min(u32::from(cidr.last_address())),
+                    country,
+                };
+                match spans.last_mut() {
+                    Some(last) if last.country == span.country && last.end + 1 == span.start => {
+                        last.end = span.end;
+                    }
+                    _ => spans.push(span),
+                }
+            }
+        }
+
+        spans
+    }
+
+    /// Finds the network containing the IPv4 address.
+    pub fn get_ipv4_range(&self, ip_address: &Ipv4Addr) -> LookupOutcome<Ipv4Span> {
+        let span = self
+            .reader
+            .lookup(IpAddr::V4(*ip_address))
+            .ok()
+            .and_then(|result| match result.network().ok()? {
+                IpNetwork::V4(network) => Some(Ipv4Span {
+                    start: u32::from(network.network()),
+                    end: u32::from(network.broadcast()),
+                    country: self.decode_country(&result)?,
+                }),
+                IpNetwork::V6(_) => None,
+            });
+
+        match span {
+            Some(span) => LookupOutcome::Found(span),
+            None => LookupOutcome::InGap,
+        }
+    }
+
+    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> LookupOutcome<CountryCode> {
+        self.get_country(IpAddr::V4(*ip_address))
+    }
+
+    pub fn get_ipv6_country(&self, ip_address: &Ipv6Addr) -> LookupOutcome<CountryCode> {
+        self.get_country(IpAddr::V6(*ip_address))
+    }
+
+    /// The search tree doesn't tell where the address sits relative to the covered networks, so
+    /// every miss is reported as a gap.
+    fn get_country(&self, ip_address: IpAddr) -> LookupOutcome<CountryCode> {
+        // Lookup errors (e.g. IPv6 addresses against an IPv4-only database) are treated as misses
+        match self
+            .reader
+            .lookup(ip_address)
+            .ok()
+            .and_then(|result| self.decode_country(&result))
+        {
+            Some(country) => LookupOutcome::Found(country),
+            None => LookupOutcome::InGap,
+        }
+    }
+
+    fn decode_country(&self, result: &LookupResult<'_, Vec<u8>>) -> Option<CountryCode> {
+        let country: &str = result.decode_path(COUNTRY_CODE_PATH).ok().flatten()?;
+        CountryCode::new(country).filter(|country| self.countries.allows(*country))
+    }
+
+    /// Iterates over IPv4 networks with country data within `network`, in address order.
+    fn ipv4_networks(
+        &self,
+        network: IpNetwork,
+    ) -> impl Iterator<Item = (Ipv4Network, CountryCode)> + '_ {
+        self.reader
+            .within(network, Default::default())
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                let item = item.ok()?;
+                match item.network().ok()? {
+                    IpNetwork::V4(network) => Some((network, self.decode_country(&item)?)),
+                    IpNetwork::V6(_) => None,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an IPv4 MaxMind DB with 24-bit records mapping each (non-overlapping) network to
+    /// `{"country": {"iso_code": ...}}`.
+    fn build_mmdb(networks: &[(&str, &str)]) -> Vec<u8> {
+        #[derive(Clone, Copy)]
+        enum Record {
+            Empty,
+            Node(usize),
+            Data(usize),
+        }
+
+        let mut nodes = vec![[Record::Empty; 2]];
+        let mut data = vec![];
+        for (network, country) in networks {
+            let network: Ipv4Network = network.parse().unwrap();
+            let record = Record::Data(data.len());
+            data.extend(encode_map(&[(
+                "country",
+                encode_map(&[("iso_code", encode_string(country))]),
+            )]));
+
+            let bits = u32::from(network.network());
+            let mut node = 0;
+            for depth in 0..network.prefix() {
+                let bit = (bits >> (31 - depth) & 1) as usize;
+                if depth + 1 == network.prefix() {
+                    nodes[node][bit] = record;
+                } else {
+                    node = match nodes[node][bit] {
+                        Record::Node(child) => child,
+                        _ => {
+                            nodes.push([Record::Empty; 2]);
+                            nodes[node][bit] = Record::Node(nodes.len() - 1);
+                            nodes.len() - 1
+                        }
+                    };
+                }
+            }
+        }
+
+        let node_count = nodes.len();
+        let mut db = vec![];
+        for record in nodes.iter().flatten() {
+            let value = match *record {
+                Record::Empty => node_count,
+                Record::Node(child) => child,
+                Record::Data(offset) => node_count + 16 + offset,
+            };
+            db.extend(&(value as u32).to_be_bytes()[1..]);
+        }
+        db.extend([0; 16]);
+        db.extend(data);
+        db.extend(b"\xab\xcd\xefMaxMind.com");
+        db.extend(encode_map(&[
+            ("binary_format_major_version", encode_uint(5, 2, 2)),
+            ("binary_format_minor_version", encode_uint(5, 0, 2)),
+            ("build_epoch", encode_uint(9, 0, 8)),
+            ("database_type", encode_string("Test-Country")),
+            ("description", encode_map(&[])),
+            ("ip_version", encode_uint(5, 4, 2)),
+            ("languages", encode_field(11, 0, &[])),
+            ("node_count", encode_uint(6, node_count as u64, 4)),
+            ("record_size", encode_uint(5, 24, 2)),
+        ]));
+
+        db
+    }
+
+    fn encode_field(type_num: u8, size: usize, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = if type_num <= 7 {
+            vec![type_num << 5 | size as u8]
+        } else {
+            // Extended types are stored in the byte following the control byte
+            vec![size as u8, type_num - 7]
+        };
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn encode_string(value: &str) -> Vec<u8> {
+        encode_field(2, value.len(), value.as_bytes())
+    }
+
+    fn encode_uint(type_num: u8, value: u64, width: usize) -> Vec<u8> {
+        encode_field(type_num, width, &value.to_be_bytes()[8 - width..])
+    }
+
+    fn encode_map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = encode_field(7, entries.len(), &[]);
+        for (key, value) in entries {
+            bytes.extend(encode_string(key));
+            bytes.extend(value);
+        }
+        bytes
+    }
+
+    fn provider(networks: &[(&str, &str)]) -> MaxmindProvider {
+        MaxmindProvider::from_reader(
+            Reader::from_source(build_mmdb(networks)).unwrap(),
+            CountryFilter::default(),
+        )
+        .unwrap()
+    }
+
+    fn addr(ip_address: &str) -> u32 {
+        u32::from(ip_address.parse::<Ipv4Addr>().unwrap())
+    }
+
+    #[test]
+    fn test_lookup() {
+        let provider = provider(&[("1.0.0.0/22", "US"), ("1.0.4.0/24", "CN")]);
+
+        assert_eq!(provider.ipv4_range_count(), 2);
+        assert_eq!(provider.ipv6_range_count(), 0);
+        assert_eq!(
+            provider
+                .country_codes()
+                .iter()
+                .map(|country| country.to_string())
+                .collect::<Vec<_>>(),
+            ["CN", "US"]
+        );
+
+        let country = |ip_address: &str| {
+            provider
+                .get_ipv4_country(&ip_address.parse().unwrap())
+                .found()
+                .map(|country| country.to_string())
+        };
+        assert_eq!(country("1.0.0.0").as_deref(), Some("US"));
+        assert_eq!(country("1.0.3.255").as_deref(), Some("US"));
+        assert_eq!(country("1.0.4.1").as_deref(), Some("CN"));
+        assert_eq!(country("1.0.5.0"), None);
+
+        let range = provider
+            .get_ipv4_range(&"1.0.2.3".parse().unwrap())
+            .found()
+            .unwrap();
+        assert_eq!(
+            (range.start, range.end),
+            (addr("1.0.0.0"), addr("1.0.3.255"))
+        );
+    }
+
+    #[test]
+    fn test_get_ipv4_spans() {
+        let provider = provider(&[
+            ("1.0.0.0/22", "US"),
+            ("1.0.4.0/24", "CN"),
+            ("1.0.5.0/24", "CN"),
+            ("1.0.7.0/24", "CN"),
+        ]);
+        let spans = |start: &str, end: &str| {
+            provider
+                .get_ipv4_spans(addr(start), addr(end))
+                .into_iter()
+                .map(|span| (span.start, span.end, span.country.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // The range is split into several blocks within the same network, which is still reported
+        // once
+        assert_eq!(
+            spans("1.0.0.128", "1.0.2.0"),
+            [(addr("1.0.0.128"), addr("1.0.2.0"), "US".to_owned())]
+        );
+
+        // Adjacent networks of the same country are merged, but not across the gap
+        assert_eq!(
+            spans("1.0.3.0", "1.0.7.9"),
+            [
+                (addr("1.0.3.0"), addr("1.0.3.255"), "US".to_owned()),
+                (addr("1.0.4.0"), addr("1.0.5.255"), "CN".to_owned()),
+                (addr("1.0.7.0"), addr("1.0.7.9"), "CN".to_owned()),
+            ]
+        );
+
+        assert!(spans("1.0.6.0", "1.0.6.255").is_empty());
+    }
+}
@@ -1,22 +1,603 @@
 mod herrbischoff;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
-pub use herrbischoff::HerrbischoffProvider;
+use cidr::Ipv4Cidr;
+
+use crate::country::CountryCode;
+
+pub use herrbischoff::{HerrbischoffProvider, LineFormat};
 
 mod ip2location;
-pub use ip2location::Ip2locationProvider;
+pub use ip2location::{Ip2locationOptions, Ip2locationProvider};
+
+mod maxmind;
+pub use maxmind::MaxmindProvider;
+
+mod rir;
+pub use rir::RirProvider;
 
 #[derive(Debug)]
 pub enum IpgeoProvider {
     Herrbischoff(HerrbischoffProvider),
     Ip2location(Ip2locationProvider),
+    Maxmind(Box<MaxmindProvider>),
+    Rir(RirProvider),
+    /// Multiple providers tried in order, where matches they disagree on are resolved by the
+    /// policy.
+    Chained(Vec<IpgeoProvider>, ConflictPolicy),
+}
+
+/// How chained providers resolve addresses they disagree on the country of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The first provider with a match wins, without consulting the remaining ones.
+    #[default]
+    First,
+    /// The match of the provider with the given name wins, falling back to the first match.
+    Prefer(&'static str),
+    /// Addresses are reported as not found when providers matching them disagree on the country.
+    DisagreeNull,
+}
+
+/// A country found by a provider.
+#[derive(Debug, Clone)]
+pub struct CountryMatch {
+    pub country: CountryCode,
+    /// Finer-grained location, for providers that support it.
+    pub location: Option<Location>,
+    /// Name of the provider that resolved the country.
+    pub source: &'static str,
+}
+
+/// The range an IPv4 address was found in.
+#[derive(Debug, Clone)]
+pub struct RangeMatch {
+    pub span: Ipv4Span,
+    /// Name of the provider that resolved the range.
+    pub source: &'static str,
+}
+
+/// Result of looking up an address, telling apart the reasons for not finding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupOutcome<T = CountryMatch> {
+    Found(T),
+    /// The address falls between two ranges.
+    InGap,
+    /// The address comes before the first range, or no ranges are loaded.
+    BelowRange,
+    /// The address comes after the last range.
+    AboveRange,
+    /// Chained providers disagree on the country of the address.
+    Conflict,
+}
+
+/// An inclusive IPv4 address range assigned to a single country.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv4Span {
+    pub start: u32,
+    pub end: u32,
+    pub country: CountryCode,
+}
+
+/// Countries to retain when loading a database.
+#[derive(Debug, Default, Clone)]
+pub struct CountryFilter {
+    /// Only these countries are kept, unless empty.
+    pub include: Vec<CountryCode>,
+    pub exclude: Vec<CountryCode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub region: Option<String>,
+    pub city: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
 }
 
 impl IpgeoProvider {
-    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> Option<String> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Herrbischoff(_) => "herrbischoff",
+            Self::Ip2location(_) => "ip2location",
+            Self::Maxmind(_) => "maxmind",
+            Self::Rir(_) => "rir",
+            Self::Chained(..) => "chained",
+        }
+    }
+
+    /// Names of the individual providers, in the order they're tried.
+    pub fn provider_names(&self) -> Vec<&'static str> {
+        match self {
+            Self::Chained(providers, _) => {
+                providers.iter().flat_map(Self::provider_names).collect()
+            }
+            _ => vec![self.name()],
+        }
+    }
+
+    pub fn ipv4_range_count(&self) -> usize {
+        match self {
+            Self::Herrbischoff(provider) => provider.ipv4_range_count(),
+            Self::Ip2location(provider) => provider.ipv4_range_count(),
+            Self::Maxmind(provider) => provider.ipv4_range_count(),
+            Self::Rir(provider) => provider.ipv4_range_count(),
+            Self::Chained(providers, _) => providers.iter().map(Self::ipv4_range_count).sum(),
+        }
+    }
+
+    pub fn ipv6_range_count(&self) -> usize {
+        match self {
+            Self::Herrbischoff(provider) => provider.ipv6_range_count(),
+            Self::Ip2location(provider) => provider.ipv6_range_count(),
+            Self::Maxmind(provider) => provider.ipv6_range_count(),
+            Self::Rir(provider) => provider.ipv6_range_count(),
+            Self::Chained(providers, _) => providers.iter().map(Self::ipv6_range_count).sum(),
+        }
+    }
+
+    /// Lists the distinct countries present in the dataset, sorted by code.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        match self {
+            Self::Herrbischoff(provider) => provider.country_codes(),
+            Self::Ip2location(provider) => provider.country_codes(),
+            Self::Maxmind(provider) => provider.country_codes(),
+            Self::Rir(provider) => provider.country_codes(),
+            Self::Chained(providers, _) => {
+                let mut countries = providers
+                    .iter()
+                    .flat_map(Self::country_codes)
+                    .collect::<Vec<_>>();
+                countries.sort_unstable();
+                countries.dedup();
+                countries
+            }
+        }
+    }
+
+    /// Lists the IPv4 CIDR blocks assigned to the country, sorted by address.
+    pub fn get_ipv4_cidrs(&self, country: CountryCode) -> Vec<Ipv4Cidr> {
+        match self {
+            Self::Herrbischoff(provider) => provider.get_ipv4_cidrs(country),
+            Self::Ip2location(provider) => provider.get_ipv4_cidrs(country),
+            Self::Maxmind(provider) => provider.get_ipv4_cidrs(country),
+            Self::Rir(provider) => provider.get_ipv4_cidrs(country),
+            Self::Chained(providers, _) => {
+                let mut cidrs = providers
+                    .iter()
+                    .flat_map(|provider| provider.get_ipv4_cidrs(country))
+                    .collect::<Vec<_>>();
+                cidrs.sort_unstable();
+                cidrs.dedup();
+                cidrs
+            }
+        }
+    }
+
+    /// Lists the countries assigned within the inclusive `[start, end]` IPv4 range, clipped to the
+    /// range and sorted by address. Adjacent spans of the same country are merged.
+    pub fn get_ipv4_spans(&self, start: u32, end: u32) -> Vec<Ipv4Span> {
+        let mut spans = match self {
+            Self::Herrbischoff(provider) => provider.get_ipv4_spans(start, end),
+            Self::Ip2location(provider) => provider.get_ipv4_spans(start, end),
+            Self::Maxmind(provider) => provider.get_ipv4_spans(start, end),
+            Self::Rir(provider) => provider.get_ipv4_spans(start, end),
+            Self::Chained(providers, _) => {
+                // Earlier providers take precedence, so later ones only fill in the gaps
+                let mut spans: Vec<Ipv4Span> = vec![];
+                let mut gaps = vec![(start, end)];
+
+                for provider in providers.iter() {
+                    let mut remaining_gaps = vec![];
+
+                    for (gap_start, gap_end) in gaps.into_iter() {
+                        let mut cursor = Some(gap_start);
+
+                        for span in provider.get_ipv4_spans(gap_start, gap_end) {
+                            if let Some(cursor) = cursor {
+                                if span.start > cursor {
+                                    remaining_gaps.push((cursor, span.start - 1));
+                                }
+                            }
+                            cursor = span.end.checked_add(1);
+                            spans.push(span);
+                        }
+
+                        if let Some(cursor) = cursor {
+                            if cursor <= gap_end {
+                                remaining_gaps.push((cursor, gap_end));
+                            }
+                        }
+                    }
+
+                    gaps = remaining_gaps;
+                    if gaps.is_empty() {
+                        break;
+                    }
+                }
+
+                spans.sort_unstable_by_key(|span| span.start);
+                spans
+            }
+        };
+
+        spans.dedup_by(|next, prev| {
+            if prev.country == next.country && prev.end.checked_add(1) == Some(next.start) {
+                prev.end = next.end;
+                true
+            } else {
+                false
+            }
+        });
+
+        spans
+    }
+
+    /// Finds the country of the IPv4 address, reporting which provider resolved it.
+    pub fn match_ipv4(&self, ip_address: &Ipv4Addr) -> LookupOutcome {
+        match self {
+            Self::Herrbischoff(provider) => provider
+                .get_ipv4_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Ip2location(provider) => provider
+                .get_ipv4_country(ip_address)
+                .map(|country| self.country_match(country, provider.get_ipv4_location(ip_address))),
+            Self::Maxmind(provider) => provider
+                .get_ipv4_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Rir(provider) => provider
+                .get_ipv4_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Chained(providers, policy) => Self::resolve_conflicts(
+                *policy,
+                providers
+                    .iter()
+                    .map(|provider| provider.match_ipv4(ip_address)),
+            ),
+        }
+    }
+
+    /// Finds the country of the IPv4 address.
+    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> LookupOutcome<CountryCode> {
+        self.match_ipv4(ip_address).map(|item| item.country)
+    }
+
+    /// Lists the distinct countries any of the providers assigns the IPv4 address to, in the
+    /// order the providers are tried. Unlike [`Self::get_ipv4_country`], disagreements between
+    /// chained providers are kept rather than resolved.
+    pub fn get_ipv4_countries(&self, ip_address: &Ipv4Addr) -> Vec<CountryCode> {
+        match self {
+            Self::Chained(providers, _) => {
+                let mut countries = vec![];
+                for country in providers
+                    .iter()
+                    .flat_map(|provider| provider.get_ipv4_countries(ip_address))
+                {
+                    if !countries.contains(&country) {
+                        countries.push(country);
+                    }
+                }
+                countries
+            }
+            _ => self
+                .get_ipv4_country(ip_address)
+                .found()
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Finds the range containing the IPv4 address, as stored by the provider that resolved it.
+    pub fn get_ipv4_range(&self, ip_address: &Ipv4Addr) -> LookupOutcome<RangeMatch> {
+        let span = match self {
+            Self::Herrbischoff(provider) => provider.get_ipv4_range(ip_address),
+            Self::Ip2location(provider) => provider.get_ipv4_range(ip_address),
+            Self::Maxmind(provider) => provider.get_ipv4_range(ip_address),
+            Self::Rir(provider) => provider.get_ipv4_range(ip_address),
+            Self::Chained(providers, _) => {
+                return Self::first_found(
+                    providers
+                        .iter()
+                        .map(|provider| provider.get_ipv4_range(ip_address)),
+                )
+            }
+        };
+
+        span.map(|span| RangeMatch {
+            span,
+            source: self.name(),
+        })
+    }
+
+    /// Finds the closest IP2Location ranges below and above the IPv4 address. Only IP2Location is
+    /// supported so far, so there are no neighbors for other providers.
+    pub fn get_ipv4_neighbors(
+        &self,
+        ip_address: &Ipv4Addr,
+    ) -> (Option<Ipv4Span>, Option<Ipv4Span>) {
+        match self {
+            Self::Ip2location(provider) => provider.get_ipv4_neighbors(ip_address),
+            Self::Chained(providers, _) => providers
+                .iter()
+                .map(|provider| provider.get_ipv4_neighbors(ip_address))
+                .find(|(below, above)| below.is_some() || above.is_some())
+                .unwrap_or_default(),
+            _ => (None, None),
+        }
+    }
+
+    /// Finds the country of the IPv6 address.
+    pub fn get_ipv6_country(&self, ip_address: &Ipv6Addr) -> LookupOutcome<CountryCode> {
+        self.match_ipv6(ip_address).map(|item| item.country)
+    }
+
+    /// Finds the country of the IPv6 address, reporting which provider resolved it.
+    pub fn match_ipv6(&self, ip_address: &Ipv6Addr) -> LookupOutcome {
+        match self {
+            Self::Herrbischoff(provider) => provider
+                .get_ipv6_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Ip2location(provider) => provider
+                .get_ipv6_country(ip_address)
+                .map(|country| self.country_match(country, provider.get_ipv6_location(ip_address))),
+            Self::Maxmind(provider) => provider
+                .get_ipv6_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Rir(provider) => provider
+                .get_ipv6_country(ip_address)
+                .map(|country| self.country_match(country, None)),
+            Self::Chained(providers, policy) => Self::resolve_conflicts(
+                *policy,
+                providers
+                    .iter()
+                    .map(|provider| provider.match_ipv6(ip_address)),
+            ),
+        }
+    }
+
+    /// Returns the first match, or the miss reported by the first provider if none matches.
+    fn first_found<T>(outcomes: impl Iterator<Item = LookupOutcome<T>>) -> LookupOutcome<T> {
+        let mut first_miss = None;
+        for outcome in outcomes {
+            match outcome {
+                LookupOutcome::Found(_) => return outcome,
+                _ => {
+                    first_miss.get_or_insert(outcome);
+                }
+            }
+        }
+
+        first_miss.unwrap_or(LookupOutcome::BelowRange)
+    }
+
+    /// Picks the match of chained providers according to the policy. Misses are resolved like
+    /// with [`Self::first_found`].
+    fn resolve_conflicts(
+        policy: ConflictPolicy,
+        outcomes: impl Iterator<Item = LookupOutcome>,
+    ) -> LookupOutcome {
+        if policy == ConflictPolicy::First {
+            return Self::first_found(outcomes);
+        }
+
+        let outcomes = outcomes.collect::<Vec<_>>();
+        let mut matches = outcomes.iter().filter_map(|outcome| match outcome {
+            LookupOutcome::Found(country_match) => Some(country_match),
+            _ => None,
+        });
+        let Some(first_match) = matches.clone().next() else {
+            return Self::first_found(outcomes.into_iter());
+        };
+
+        match policy {
+            ConflictPolicy::First => unreachable!("handled above"),
+            ConflictPolicy::Prefer(name) => LookupOutcome::Found(
+                matches
+                    .find(|item| item.source == name)
+                    .unwrap_or(first_match)
+                    .clone(),
+            ),
+            ConflictPolicy::DisagreeNull => {
+                if matches.any(|item| item.country != first_match.country) {
+                    LookupOutcome::Conflict
+                } else {
+                    LookupOutcome::Found(first_match.clone())
+                }
+            }
+        }
+    }
+
+    fn country_match(&self, country: CountryCode, location: Option<Location>) -> CountryMatch {
+        CountryMatch {
+            country,
+            location,
+            source: self.name(),
+        }
+    }
+}
+
+impl CountryFilter {
+    pub fn allows(&self, country: CountryCode) -> bool {
+        (self.include.is_empty() || self.include.contains(&country))
+            && !self.exclude.contains(&country)
+    }
+}
+
+impl<T> LookupOutcome<T> {
+    pub fn map<U, F>(self, f: F) -> LookupOutcome<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            Self::Found(value) => LookupOutcome::Found(f(value)),
+            Self::InGap => LookupOutcome::InGap,
+            Self::BelowRange => LookupOutcome::BelowRange,
+            Self::AboveRange => LookupOutcome::AboveRange,
+            Self::Conflict => LookupOutcome::Conflict,
+        }
+    }
+
+    pub fn found(self) -> Option<T> {
+        match self {
+            Self::Found(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Machine-readable reason for a miss, or `None` if the address was found.
+    pub fn miss_reason(&self) -> Option<&'static str> {
         match self {
-            Self::Herrbischoff(provider) => provider.get_ipv4_country(ip_address),
-            Self::Ip2location(provider) => provider.get_ipv4_country(ip_address),
+            Self::Found(_) => None,
+            Self::InGap => Some("in_gap"),
+            Self::BelowRange => Some("below_range"),
+            Self::AboveRange => Some("above_range"),
+            Self::Conflict => Some("conflict"),
+        }
+    }
+}
+
+/// Leading bytes of gzip-compressed files.
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Inclusive range of addresses, as stored in the sorted range lists of the databases.
+pub(crate) trait AddressRange {
+    type Value: Ord + Copy;
+
+    fn start(&self) -> Self::Value;
+
+    fn end(&self) -> Self::Value;
+}
+
+/// Finds the range containing `ip_value`, which can only be the last range starting at or below
+/// it, as ranges are sorted and disjoint.
+pub(crate) fn find_range<R>(ranges: &[R], ip_value: R::Value) -> LookupOutcome<&R>
+where
+    R: AddressRange,
+{
+    let ind = ranges.partition_point(|range| range.start() <= ip_value);
+
+    match ind.checked_sub(1).map(|ind| &ranges[ind]) {
+        Some(range) if range.end() >= ip_value => LookupOutcome::Found(range),
+        // `ip_value` is smaller even than the first record
+        None => LookupOutcome::BelowRange,
+        // `ip_value` is larger even than the last record
+        Some(_) if ind == ranges.len() => LookupOutcome::AboveRange,
+        // `ip_value` falls in the gap between two ranges
+        Some(_) => LookupOutcome::InGap,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Herrbischoff and IP2Location providers disagreeing on `1.0.0.0/24` and agreeing on
+    /// `1.0.1.0/24`, while only IP2Location covers `1.0.2.0/24`.
+    fn chained(policy: ConflictPolicy) -> IpgeoProvider {
+        let herrbischoff = HerrbischoffProvider::from_bytes(
+            &[("us", b"1.0.0.0/24\n1.0.1.0/24\n".as_slice())],
+            &[],
+            &CountryFilter::default(),
+        )
+        .unwrap();
+        let ip2location = Ip2locationProvider::from_readers(
+            Some(
+                b"\"16777216\",\"16777471\",\"AU\",\"Australia\"\n\
+                  \"16777472\",\"16777727\",\"US\",\"United States of America\"\n\
+                  \"16777728\",\"16777983\",\"JP\",\"Japan\"\n"
+                    .as_slice(),
+            ),
+            None::<&[u8]>,
+            &Ip2locationOptions::default(),
+        )
+        .unwrap();
+
+        IpgeoProvider::Chained(
+            vec![
+                IpgeoProvider::Herrbischoff(herrbischoff),
+                IpgeoProvider::Ip2location(ip2location),
+            ],
+            policy,
+        )
+    }
+
+    fn lookup(provider: &IpgeoProvider, ip_address: &str) -> LookupOutcome<(String, &'static str)> {
+        provider
+            .match_ipv4(&ip_address.parse().unwrap())
+            .map(|item| (item.country.to_string(), item.source))
+    }
+
+    fn found(country: &str, source: &'static str) -> LookupOutcome<(String, &'static str)> {
+        LookupOutcome::Found((country.to_owned(), source))
+    }
+
+    #[test]
+    fn test_all_countries() {
+        let provider = chained(ConflictPolicy::First);
+        let countries = |ip_address: &str| {
+            provider
+                .get_ipv4_countries(&ip_address.parse().unwrap())
+                .iter()
+                .map(CountryCode::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(countries("1.0.0.1"), ["US", "AU"]);
+        assert_eq!(countries("1.0.1.1"), ["US"]);
+        assert_eq!(countries("1.0.2.1"), ["JP"]);
+        assert!(countries("1.0.3.1").is_empty());
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let cases = [
+            (
+                ConflictPolicy::First,
+                [
+                    found("US", "herrbischoff"),
+                    found("US", "herrbischoff"),
+                    found("JP", "ip2location"),
+                ],
+            ),
+            (
+                ConflictPolicy::Prefer("ip2location"),
+                [
+                    found("AU", "ip2location"),
+                    found("US", "ip2location"),
+                    found("JP", "ip2location"),
+                ],
+            ),
+            (
+                ConflictPolicy::Prefer("maxmind"),
+                [
+                    found("US", "herrbischoff"),
+                    found("US", "herrbischoff"),
+                    found("JP", "ip2location"),
+                ],
+            ),
+            (
+                ConflictPolicy::DisagreeNull,
+                [
+                    LookupOutcome::Conflict,
+                    found("US", "herrbischoff"),
+                    found("JP", "ip2location"),
+                ],
+            ),
+        ];
+        for (policy, expected) in cases {
+            let provider = chained(policy);
+            for (ip_address, expected) in
+                ["1.0.0.1", "1.0.1.1", "1.0.2.1"].into_iter().zip(expected)
+            {
+                assert_eq!(
+                    lookup(&provider, ip_address),
+                    expected,
+                    "{:?} {}",
+                    policy,
+                    ip_address
+                );
+            }
+            assert_eq!(lookup(&provider, "1.0.3.1"), LookupOutcome::AboveRange);
         }
     }
 }
@@ -0,0 +1,427 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, BufReader},
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use cidr::Ipv4Cidr;
+use log::{info, warn};
+use rayon::prelude::*;
+
+use super::{find_range, AddressRange, CountryFilter, Ipv4Span, LookupOutcome};
+use crate::{country::CountryCode, util::range_to_cidrs};
+
+/// Allocations listed in the delegated statistics files published by the regional internet
+/// registries, e.g. `delegated-ripencc-extended-latest`.
+#[derive(Debug)]
+pub struct RirProvider {
+    ipv4_ranges: Vec<RirRange<u32>>,
+    ipv6_ranges: Vec<RirRange<u128>>,
+}
+
+#[derive(Debug)]
+struct RirRange<T> {
+    start: T,
+    end: T,
+    country: CountryCode,
+}
+
+impl<T: Ord + Copy> AddressRange for RirRange<T> {
+    type Value = T;
+
+    fn start(&self) -> T {
+        self.start
+    }
+
+    fn end(&self) -> T {
+        self.end
+    }
+}
+
+impl RirProvider {
+    /// Loads all `delegated-*` files in the directory, skipping `.md5` and `.asc` files published
+    /// alongside them. Only `allocated` and `assigned` records are kept.
+    ///
+    /// Consecutive ranges of the same country are merged. Ranges overlapping an earlier one, which
+    /// can happen when registries disagree during transfers, are skipped with a warning.
+    pub fn from_dir(dir: &Path, countries: &CountryFilter) -> anyhow::Result<Self> {
+        let mut file_paths = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let file_path = entry?.path();
+            let is_stats_file = file_path
+                .file_name()
+                .and_then(|value| value.to_str())
+                .is_some_and(|value| {
+                    value.starts_with("delegated-")
+                        && !value.ends_with(".md5")
+                        && !value.ends_with(".asc")
+                });
+            if is_stats_file && file_path.is_file() {
+                file_paths.push(file_path);
+            }
+        }
+        if file_paths.is_empty() {
+            anyhow::bail!("no delegated-* files found under {}", dir.display());
+        }
+
+        // Sorted so that the registry winning an overlap doesn't depend on directory order
+        file_paths.sort();
+
+        let file_ranges = file_paths
+            .par_iter()
+            .map(|file_path| {
+                let mut ipv4_ranges = vec![];
+                let mut ipv6_ranges = vec![];
+
+                let file = std::fs::File::open(file_path)?;
+                parse_records(
+                    &file_path.display().to_string(),
+                    BufReader::new(file),
+                    countries,
+                    &mut ipv4_ranges,
+                    &mut ipv6_ranges,
+                )?;
+
+                Ok((ipv4_ranges, ipv6_ranges))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut ipv4_ranges = vec![];
+        let mut ipv6_ranges = vec![];
+        for (file_ipv4_ranges, file_ipv6_ranges) in file_ranges.into_iter() {
+            ipv4_ranges.extend(file_ipv4_ranges);
+            ipv6_ranges.extend(file_ipv6_ranges);
+        }
+
+        let ipv4_ranges = sort_ranges(ipv4_ranges);
+        let ipv6_ranges = sort_ranges(ipv6_ranges);
+
+        info!(
+            "Loaded {} IPv4 and {} IPv6 ranges from {} RIR files under {}",
+            ipv4_ranges.len(),
+            ipv6_ranges.len(),
+            file_paths.len(),
+            dir.display()
+        );
+
+        Ok(Self {
+            ipv4_ranges,
+            ipv6_ranges,
+        })
+    }
+
+    pub fn ipv4_range_count(&self) -> usize {
+        self.ipv4_ranges.len()
+    }
+
+    pub fn ipv6_range_count(&self) -> usize {
+        self.ipv6_ranges.len()
+    }
+
+    /// Lists the distinct countries with any IPv4 or IPv6 ranges, sorted by code.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        let countries = self
+            .ipv4_ranges
+            .iter()
+            .map(|range| range.country)
+            .chain(self.ipv6_ranges.iter().map(|range| range.country))
+            .collect::<BTreeSet<_>>();
+
+        countries.into_iter().collect()
+    }
+
+    /// Lists the IPv4 ranges assigned to the country as a minimal set of CIDR blocks.
+    pub fn get_ipv4_cidrs(&self, country: CountryCode) -> Vec<Ipv4Cidr> {
+        self.ipv4_ranges
+            .iter()
+            .filter(|range| range.country == country)
+            .flat_map(|range| range_to_cidrs(range.start, range.end))
+            .collect()
+    }
+
+    pub fn get_ipv4_spans(&self, start: u32, end: u32) -> Vec<Ipv4Span> {
+        // Ranges are disjoint and sorted, so their `end` values are sorted too
+        let first = self.ipv4_ranges.partition_point(|range| range.end < start);
+
+        self.ipv4_ranges[first..]
+            .iter()
+            .take_while(|range| range.start <= end)
+            .map(|range| Ipv4Span {
+                start: range.start.max(start),
+                end: range.end.min(end),
+                country: range.country,
+            })
+            .collect()
+    }
+
+    /// Finds the range containing the IPv4 address.
+    pub fn get_ipv4_range(&self, ip_address: &Ipv4Addr) -> LookupOutcome<Ipv4Span> {
+        find_range(&self.ipv4_ranges, u32::from(*ip_address)).map(|range| Ipv4Span {
+            start: range.start,
+            end: range.end,
+            country: range.country,
+        })
+    }
+
+    pub fn get_ipv4_country(&self, ip_address: &Ipv4Addr) -> LookupOutcome<CountryCode> {
+        find_range(&self.ipv4_ranges, u32::from(*ip_address)).map(|range| range.country)
+    }
+
+    pub fn get_ipv6_country(&self, ip_address: &Ipv6Addr) -> LookupOutcome<CountryCode> {
+        find_range(&self.ipv6_ranges, u128::from(*ip_address)).map(|range| range.country)
+    }
+}
+
+/// Parses a delegated statistics file. Records look like:
+///
+/// ```text
+/// registry|cc|type|start|value|date|status[|opaque-id[|extensions...]]
+/// ```
+///
+/// where `value` is the number of addresses for `ipv4` records, and the prefix length for `ipv6`
+/// records. The version line, summary lines (with `*` as `cc`), `asn` records and `#` comments are
+/// skipped. `source_name` identifies the file in error messages.
+fn parse_records<R>(
+    source_name: &str,
+    reader: R,
+    countries: &CountryFilter,
+    ipv4_ranges: &mut Vec<RirRange<u32>>,
+    ipv6_ranges: &mut Vec<RirRange<u128>>,
+) -> anyhow::Result<()>
+where
+    R: BufRead,
+{
+    for (ind, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.split('|').collect::<Vec<_>>();
+        let is_address_record = fields
+            .get(2)
+            .is_some_and(|value| *value == "ipv4" || *value == "ipv6");
+        if !is_address_record || fields[1] == "*" {
+            continue;
+        }
+
+        let invalid_record = |reason: &str| {
+            anyhow::anyhow!(
+                "invalid RIR record at {}:{}: {:?} ({})",
+                source_name,
+                ind + 1,
+                line,
+                reason
+            )
+        };
+
+        if fields.len() < 7 {
+            return Err(invalid_record("expected at least 7 fields"));
+        }
+        if fields[6] != "allocated" && fields[6] != "assigned" {
+            continue;
+        }
+
+        let country =
+            CountryCode::new(fields[1]).ok_or_else(|| invalid_record("invalid country code"))?;
+        if !countries.allows(country) {
+            continue;
+        }
+
+        if fields[2] == "ipv4" {
+            let start = u32::from(
+                fields[3]
+                    .parse::<Ipv4Addr>()
+                    .map_err(|_| invalid_record("invalid start address"))?,
+            );
+            let count = fields[4]
+                .parse::<u32>()
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| invalid_record("invalid address count"))?;
+            let end = start
+                .checked_add(count - 1)
+                .ok_or_else(|| invalid_record("range exceeds the address space"))?;
+
+            ipv4_ranges.push(RirRange {
+                start,
+                end,
+                country,
+            });
+        } else {
+            let start = u128::from(
+                fields[3]
+                    .parse::<Ipv6Addr>()
+                    .map_err(|_| invalid_record("invalid start address"))?,
+            );
+            let prefix_len = fields[4]
+                .parse::<u32>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= 128)
+                .ok_or_else(|| invalid_record("invalid prefix length"))?;
+            let host_mask = u128::MAX.checked_shr(prefix_len).unwrap_or(0);
+            if start & host_mask != 0 {
+                return Err(invalid_record("start address has host bits set"));
+            }
+
+            ipv6_ranges.push(RirRange {
+                start,
+                end: start | host_mask,
+                country,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Sorts the ranges, dropping the ones overlapping an earlier range and merging consecutive ranges
+/// of the same country.
+fn sort_ranges<T>(mut ranges: Vec<RirRange<T>>) -> Vec<RirRange<T>>
+where
+    T: Ord + Copy + std::fmt::Display + std::ops::Add<Output = T> + From<u8>,
+{
+    // Stable so that ranges sharing a `start` keep the order of the files they came from
+    ranges.sort_by_key(|range| range.start);
+
+    let mut sorted_ranges: Vec<RirRange<T>> = Vec::with_capacity(ranges.len());
+    let mut overlap_count = 0;
+    for range in ranges.into_iter() {
+        if let Some(last_range) = sorted_ranges.last_mut() {
+            if last_range.end >= range.start {
+                overlap_count += 1;
+                continue;
+            }
+
+            // `end` can't be the maximum value here, as it's below `range.start`
+            if last_range.country == range.country && last_range.end + T::from(1) == range.start {
+                last_range.end = range.end;
+                continue;
+            }
+        }
+
+        sorted_ranges.push(range);
+    }
+
+    if overlap_count > 0 {
+        warn!(
+            "Skipped {} RIR ranges overlapping earlier ones",
+            overlap_count
+        );
+    }
+
+    sorted_ranges.shrink_to_fit();
+    sorted_ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: &str = "\
+2|apnic|20240101|4|19830613|20231231|+1000
+# Comment
+apnic|*|ipv4|*|3|summary
+apnic|*|ipv6|*|1|summary
+apnic|AU|ipv4|1.0.0.0|256|20110811|assigned|A91872ED
+apnic|AU|ipv4|1.0.1.0|256|20110414|allocated|A92E1062
+apnic|CN|ipv4|1.0.2.0|512|20110414|allocated|A92E1062
+apnic||ipv4|1.0.8.0|256||available|
+apnic|JP|asn|173|1|20020801|allocated|A91A7381
+apnic|JP|ipv6|2001:200::|35|19990813|allocated|A91A7381
+";
+
+    fn provider(stats: &str) -> RirProvider {
+        let mut ipv4_ranges = vec![];
+        let mut ipv6_ranges = vec![];
+        parse_records(
+            "delegated-apnic-extended-latest",
+            stats.as_bytes(),
+            &CountryFilter::default(),
+            &mut ipv4_ranges,
+            &mut ipv6_ranges,
+        )
+        .unwrap();
+
+        RirProvider {
+            ipv4_ranges: sort_ranges(ipv4_ranges),
+            ipv6_ranges: sort_ranges(ipv6_ranges),
+        }
+    }
+
+    #[test]
+    fn test_parse_records() {
+        let provider = provider(STATS);
+
+        // The two consecutive AU ranges are merged
+        assert_eq!(provider.ipv4_range_count(), 2);
+        assert_eq!(provider.ipv6_range_count(), 1);
+
+        let country = |ip_address: &str| {
+            provider
+                .get_ipv4_country(&ip_address.parse().unwrap())
+                .found()
+                .map(|country| country.to_string())
+        };
+        assert_eq!(country("1.0.0.0").as_deref(), Some("AU"));
+        assert_eq!(country("1.0.1.255").as_deref(), Some("AU"));
+        assert_eq!(country("1.0.2.0").as_deref(), Some("CN"));
+        assert_eq!(country("1.0.3.255").as_deref(), Some("CN"));
+        assert_eq!(country("1.0.4.0"), None);
+        assert_eq!(country("1.0.8.1"), None);
+
+        let ipv6_country = |ip_address: &str| {
+            provider
+                .get_ipv6_country(&ip_address.parse().unwrap())
+                .found()
+                .map(|country| country.to_string())
+        };
+        assert_eq!(ipv6_country("2001:200::1").as_deref(), Some("JP"));
+        assert_eq!(
+            ipv6_country("2001:200:1fff:ffff:ffff:ffff:ffff:ffff").as_deref(),
+            Some("JP")
+        );
+        assert_eq!(ipv6_country("2001:200:2000::"), None);
+    }
+
+    #[test]
+    fn test_overlapping_ranges() {
+        let provider = provider(
+            "\
+arin|US|ipv4|1.0.0.0|512|20110811|assigned|
+ripencc|DE|ipv4|1.0.1.0|256|20110811|assigned|
+",
+        );
+
+        assert_eq!(provider.ipv4_range_count(), 1);
+        assert_eq!(
+            provider
+                .get_ipv4_country(&"1.0.1.0".parse().unwrap())
+                .found()
+                .map(|country| country.to_string())
+                .as_deref(),
+            Some("US")
+        );
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        let mut ipv4_ranges = vec![];
+        let mut ipv6_ranges = vec![];
+        let result = parse_records(
+            "delegated-apnic-extended-latest",
+            "apnic|AU|ipv4|1.0.0.0|0|20110811|assigned|\n".as_bytes(),
+            &CountryFilter::default(),
+            &mut ipv4_ranges,
+            &mut ipv6_ranges,
+        );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("delegated-apnic-extended-latest:1"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    num::{NonZeroU32, NonZeroUsize},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use poem::{
+    http::{header, StatusCode},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+use ipgeod::client_ip;
+
+/// Number of tracked clients above which the least recently seen ones get evicted.
+const MAX_TRACKED_CLIENTS: NonZeroUsize = NonZeroUsize::new(65536).unwrap();
+
+/// Per-client token bucket rate limiter. Each client can burst up to one second worth of requests.
+///
+/// IPv6 clients are tracked by their /64 prefix, as a single host is commonly handed a whole /64
+/// and could otherwise dodge the limit by rotating addresses.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    requests_per_second: f64,
+    trust_proxy: bool,
+    buckets: Arc<Mutex<LruCache<IpAddr, Bucket>>>,
+}
+
+pub struct RateLimitEndpoint<E> {
+    inner: E,
+    limit: RateLimit,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimit {
+    pub fn new(requests_per_second: NonZeroU32, trust_proxy: bool) -> Self {
+        Self {
+            requests_per_second: requests_per_second.get() as f64,
+            trust_proxy,
+            buckets: Arc::new(Mutex::new(LruCache::new(MAX_TRACKED_CLIENTS))),
+        }
+    }
+
+    /// Takes a token from the client's bucket. If the bucket is empty, returns how long it takes
+    /// for a token to become available instead.
+    fn try_acquire(&self, client: IpAddr) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let bucket = buckets.get_or_insert_mut(client_key(client), || Bucket {
+            tokens: self.requests_per_second,
+            updated_at: now,
+        });
+        bucket.tokens = self.refilled_tokens(bucket, now);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.requests_per_second,
+            ))
+        }
+    }
+
+    fn refilled_tokens(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+
+        (bucket.tokens + elapsed * self.requests_per_second).min(self.requests_per_second)
+    }
+}
+
+/// Address the client is tracked by: IPv4 addresses as is, and IPv6 addresses by their /64.
+fn client_key(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V4(_) => client,
+        IpAddr::V6(ip_address) => match ip_address.to_ipv4_mapped() {
+            Some(ipv4_address) => IpAddr::V4(ipv4_address),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(ip_address) & !(u128::MAX >> 64))),
+        },
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RateLimit {
+    type Output = RateLimitEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RateLimitEndpoint {
+            inner: ep,
+            limit: self.clone(),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for RateLimitEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        // Requests without a resolvable client address are let through
+        if let Some(client) = client_ip::client_ip(&req, self.limit.trust_proxy) {
+            if let Err(retry_after) = self.limit.try_acquire(client) {
+                // `Retry-After` only takes whole seconds, so it's rounded up to avoid retrying
+                // too early
+                let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+                return Ok(Response::builder()
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .header(header::RETRY_AFTER, retry_after)
+                    .finish());
+            }
+        }
+
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit() -> RateLimit {
+        RateLimit::new(NonZeroU32::new(2).unwrap(), false)
+    }
+
+    #[test]
+    fn test_ipv6_clients_share_their_64() {
+        let limit = limit();
+        let client = |ip_address: &str| ip_address.parse::<IpAddr>().unwrap();
+
+        assert!(limit.try_acquire(client("2001:db8::1")).is_ok());
+        assert!(limit.try_acquire(client("2001:db8::ffff:2")).is_ok());
+        assert!(limit.try_acquire(client("2001:db8::3")).is_err());
+
+        // Other /64s and IPv4 clients have buckets of their own
+        assert!(limit.try_acquire(client("2001:db8:0:1::1")).is_ok());
+        assert!(limit.try_acquire(client("192.0.2.1")).is_ok());
+        assert!(limit.try_acquire(client("::ffff:192.0.2.1")).is_ok());
+        assert!(limit.try_acquire(client("192.0.2.1")).is_err());
+    }
+
+    #[test]
+    fn test_least_recently_seen_client_is_evicted() {
+        let limit = limit();
+        let client = |index: usize| IpAddr::V4((index as u32).into());
+
+        // Exhausts the first client's bucket, then makes it the most recently seen one
+        assert!(limit.try_acquire(client(0)).is_ok());
+        assert!(limit.try_acquire(client(0)).is_ok());
+        for index in 1..MAX_TRACKED_CLIENTS.get() {
+            assert!(limit.try_acquire(client(index)).is_ok());
+        }
+        assert!(limit.try_acquire(client(0)).is_err());
+
+        // Tracking one more client evicts the least recently seen one instead
+        assert!(limit.try_acquire(client(MAX_TRACKED_CLIENTS.get())).is_ok());
+        let buckets = limit.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), MAX_TRACKED_CLIENTS.get());
+        assert!(!buckets.contains(&client(1)));
+        assert!(buckets.contains(&client(0)));
+    }
+}
@@ -0,0 +1,61 @@
+use poem::{http::HeaderValue, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Longest incoming request ID that is honored, so that clients can't bloat log lines.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Tags every request with an `X-Request-ID`, keeping the one sent by the client if it's sensible
+/// and generating a random UUID otherwise. The ID is set on the request for logging further in,
+/// and echoed back on the response, including error responses.
+#[derive(Debug, Clone, Default)]
+pub struct RequestId;
+
+pub struct RequestIdEndpoint<E> {
+    inner: E,
+}
+
+impl<E: Endpoint> Middleware<E> for RequestId {
+    type Output = RequestIdEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestIdEndpoint { inner: ep }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for RequestIdEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let request_id = match req.headers().get(X_REQUEST_ID) {
+            Some(value) if is_valid(value) => value.clone(),
+            _ => HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("UUIDs are valid header values"),
+        };
+        req.headers_mut().insert(X_REQUEST_ID, request_id.clone());
+
+        let mut resp = match self.inner.call(req).await {
+            Ok(resp) => resp.into_response(),
+            Err(err) => err.into_response(),
+        };
+        resp.headers_mut().insert(X_REQUEST_ID, request_id);
+
+        Ok(resp)
+    }
+}
+
+/// ID of the request, as set by [`RequestId`].
+pub fn request_id(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Accepts non-empty IDs of printable ASCII, which keeps them safe to log and echo.
+fn is_valid(value: &HeaderValue) -> bool {
+    let bytes = value.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= MAX_REQUEST_ID_LEN
+        && bytes.iter().all(|byte| byte.is_ascii_graphic())
+}
@@ -0,0 +1,124 @@
+use std::{io, sync::Arc, time::Duration};
+
+use futures_util::{stream, StreamExt};
+use poem::{http::StatusCode, Body, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+use tokio::time::Instant;
+
+/// Fails requests whose handler takes longer than `timeout` to produce a response with
+/// `504 Gateway Timeout`. Responses to `streamed_paths` are mostly produced after the handler
+/// returns, so their bodies are cut off once the same deadline passes as well. Other bodies are
+/// left untouched, as they're already complete by then.
+#[derive(Debug, Clone)]
+pub struct RequestTimeout {
+    timeout: Duration,
+    streamed_paths: Arc<[&'static str]>,
+}
+
+pub struct RequestTimeoutEndpoint<E> {
+    inner: E,
+    timeout: RequestTimeout,
+}
+
+impl RequestTimeout {
+    pub fn new(timeout: Duration, streamed_paths: &[&'static str]) -> Self {
+        Self {
+            timeout,
+            streamed_paths: streamed_paths.into(),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestTimeout {
+    type Output = RequestTimeoutEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestTimeoutEndpoint {
+            inner: ep,
+            timeout: self.clone(),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for RequestTimeoutEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let deadline = Instant::now() + self.timeout.timeout;
+        let streamed = self.timeout.streamed_paths.contains(&req.uri().path());
+
+        let mut resp = match tokio::time::timeout_at(deadline, self.inner.call(req)).await {
+            Ok(result) => result?.into_response(),
+            Err(_) => return Ok(StatusCode::GATEWAY_TIMEOUT.into_response()),
+        };
+        if !streamed {
+            return Ok(resp);
+        }
+
+        // The status has already been sent by the time the deadline passes, so the body is ended
+        // with an error, which aborts the response instead of letting it look complete
+        let body = stream::unfold(
+            Some(Box::pin(resp.take_body().into_bytes_stream())),
+            move |body| async move {
+                let mut body = body?;
+                match tokio::time::timeout_at(deadline, body.next()).await {
+                    Ok(Some(chunk)) => Some((chunk, Some(body))),
+                    Ok(None) => None,
+                    Err(_) => Some((
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+                        None,
+                    )),
+                }
+            },
+        );
+        resp.set_body(Body::from_bytes_stream(body));
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{handler, test::TestClient, EndpointExt, Route};
+
+    use super::*;
+
+    /// Sends one line right away, and another one after a second.
+    #[handler]
+    fn slow_stream() -> Body {
+        Body::from_bytes_stream(stream::iter([0, 1]).then(|index| async move {
+            if index > 0 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok::<_, io::Error>(format!("line {}\n", index))
+        }))
+    }
+
+    fn client() -> TestClient<impl Endpoint> {
+        TestClient::new(
+            Route::new()
+                .at("/stream", slow_stream)
+                .at("/other", slow_stream)
+                .with(RequestTimeout::new(
+                    Duration::from_millis(100),
+                    &["/stream"],
+                )),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_streamed_body_cut_off() {
+        let resp = client().get("/stream").send().await;
+        resp.assert_status_is_ok();
+
+        let err = resp.0.into_body().into_vec().await.unwrap_err();
+        assert!(err.to_string().contains("request timed out"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_other_body_untouched() {
+        let resp = client().get("/other").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("line 0\nline 1\n").await;
+    }
+}
@@ -0,0 +1,70 @@
+//! OpenTelemetry trace export over OTLP.
+
+use std::net::IpAddr;
+
+use opentelemetry::{global, trace, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::Tracer, Resource};
+
+/// Installs a global tracer provider exporting spans to the OTLP/gRPC `endpoint` in batches, and
+/// makes incoming W3C `traceparent` headers propagate into the spans of requests. Must be called
+/// from within the Tokio runtime, which the export runs on.
+pub fn init(endpoint: &str) -> anyhow::Result<Tracer> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            opentelemetry_sdk::trace::config().with_resource(Resource::new([
+                KeyValue::new("service.name", env!("CARGO_PKG_NAME")),
+                KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+            ])),
+        )
+        .install_batch(runtime::Tokio)?;
+
+    Ok(tracer)
+}
+
+/// Flushes pending spans. Does nothing if [`init`] was never called.
+pub async fn shutdown() {
+    // Flushing blocks on the exporter, which itself runs on the runtime
+    let _ = tokio::task::spawn_blocking(global::shutdown_tracer_provider).await;
+}
+
+/// Attaches the details of a single-address lookup to the span of the current request. Does
+/// nothing if no span is active, e.g. when [`init`] was never called.
+pub fn record_lookup(
+    request_id: Option<&str>,
+    ip_address: Option<IpAddr>,
+    country: Option<&str>,
+    provider: Option<&str>,
+    outcome: &'static str,
+) {
+    trace::get_active_span(|span| {
+        if !span.is_recording() {
+            return;
+        }
+
+        if let Some(request_id) = request_id {
+            span.set_attribute(KeyValue::new("ipgeod.request_id", request_id.to_owned()));
+        }
+        if let Some(ip_address) = ip_address {
+            span.set_attribute(KeyValue::new(
+                "ipgeod.lookup.ip_address",
+                ip_address.to_string(),
+            ));
+        }
+        if let Some(country) = country {
+            span.set_attribute(KeyValue::new("ipgeod.lookup.country", country.to_owned()));
+        }
+        if let Some(provider) = provider {
+            span.set_attribute(KeyValue::new("ipgeod.lookup.provider", provider.to_owned()));
+        }
+        span.set_attribute(KeyValue::new("ipgeod.lookup.outcome", outcome));
+    });
+}
@@ -0,0 +1,259 @@
+//! Helpers shared across providers and endpoints.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use cidr::Ipv4Cidr;
+
+/// Decomposes an inclusive range into the minimal list of CIDR blocks covering it, by greedily
+/// taking the largest block aligned at the current position that doesn't go past `end`.
+pub fn range_to_cidrs(start: u32, end: u32) -> Vec<Ipv4Cidr> {
+    let mut cidrs = vec![];
+
+    // Uses `u64` to avoid overflowing at the end of the address space
+    let end = end as u64;
+    let mut current = start as u64;
+    while current <= end {
+        let mut size: u64 = if current == 0 {
+            1 << 32
+        } else {
+            1 << current.trailing_zeros()
+        };
+        while current + size - 1 > end {
+            size >>= 1;
+        }
+
+        let prefix_len = 32 - size.trailing_zeros() as u8;
+        cidrs.push(
+            Ipv4Cidr::new(Ipv4Addr::from(current as u32), prefix_len)
+                .expect("block is aligned to prefix length"),
+        );
+
+        current += size;
+    }
+
+    cidrs
+}
+
+/// Whether the address belongs to a special-purpose block that never appears in geolocation data,
+/// such as private, loopback, or link-local ranges.
+pub fn is_reserved_ipv4(ip_address: &Ipv4Addr) -> bool {
+    let octets = ip_address.octets();
+
+    ip_address.is_private()
+        || ip_address.is_loopback()
+        || ip_address.is_link_local()
+        || ip_address.is_unspecified()
+        || ip_address.is_broadcast()
+        || ip_address.is_documentation()
+        || ip_address.is_multicast()
+        // `0.0.0.0/8` ("this network")
+        || octets[0] == 0
+        // `100.64.0.0/10` (carrier-grade NAT)
+        || (octets[0] == 100 && (octets[1] & 0b1100_0000) == 0b0100_0000)
+        // `240.0.0.0/4` (reserved for future use)
+        || octets[0] >= 240
+}
+
+/// IPv6 counterpart of [`is_reserved_ipv4`].
+pub fn is_reserved_ipv6(ip_address: &Ipv6Addr) -> bool {
+    let segments = ip_address.segments();
+
+    ip_address.is_loopback()
+        || ip_address.is_unspecified()
+        || ip_address.is_multicast()
+        || ip_address.is_unique_local()
+        || ip_address.is_unicast_link_local()
+        // `2001:db8::/32` (documentation)
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+}
+
+/// The IPv4 address embedded in an IPv4-mapped (`::ffff:1.2.3.4`) or IPv4-compatible (`::1.2.3.4`)
+/// IPv6 address. `::` and `::1` are left alone, as they're the IPv6 unspecified and loopback
+/// addresses rather than embedded IPv4 ones.
+pub fn embedded_ipv4(ip_address: &Ipv6Addr) -> Option<Ipv4Addr> {
+    if ip_address.is_unspecified() || ip_address.is_loopback() {
+        return None;
+    }
+
+    ip_address.to_ipv4()
+}
+
+/// Scratch directory for tests, removed when dropped so that failing assertions don't leave it
+/// behind.
+#[cfg(test)]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates an empty directory named after `prefix` and the process ID under the system
+    /// temporary directory, clearing out leftovers of an earlier run first.
+    pub(crate) fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}", prefix, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidrs(items: &[&str]) -> Vec<Ipv4Cidr> {
+        items.iter().map(|item| item.parse().unwrap()).collect()
+    }
+
+    fn addr(value: &str) -> u32 {
+        u32::from(value.parse::<Ipv4Addr>().unwrap())
+    }
+
+    #[test]
+    fn test_single_address() {
+        assert_eq!(
+            range_to_cidrs(addr("1.2.3.4"), addr("1.2.3.4")),
+            cidrs(&["1.2.3.4/32"])
+        );
+        assert_eq!(range_to_cidrs(0, 0), cidrs(&["0.0.0.0/32"]));
+        assert_eq!(
+            range_to_cidrs(u32::MAX, u32::MAX),
+            cidrs(&["255.255.255.255/32"])
+        );
+    }
+
+    #[test]
+    fn test_full_address_space() {
+        assert_eq!(range_to_cidrs(0, u32::MAX), cidrs(&["0.0.0.0/0"]));
+    }
+
+    #[test]
+    fn test_aligned_block() {
+        assert_eq!(
+            range_to_cidrs(addr("10.0.0.0"), addr("10.255.255.255")),
+            cidrs(&["10.0.0.0/8"])
+        );
+        assert_eq!(
+            range_to_cidrs(addr("128.0.0.0"), u32::MAX),
+            cidrs(&["128.0.0.0/1"])
+        );
+    }
+
+    #[test]
+    fn test_crossing_power_of_two_boundary() {
+        assert_eq!(
+            range_to_cidrs(addr("1.2.3.255"), addr("1.2.4.0")),
+            cidrs(&["1.2.3.255/32", "1.2.4.0/32"])
+        );
+        assert_eq!(
+            range_to_cidrs(addr("127.255.255.255"), addr("128.0.0.0")),
+            cidrs(&["127.255.255.255/32", "128.0.0.0/32"])
+        );
+        assert_eq!(
+            range_to_cidrs(addr("10.0.0.1"), addr("10.0.0.14")),
+            cidrs(&[
+                "10.0.0.1/32",
+                "10.0.0.2/31",
+                "10.0.0.4/30",
+                "10.0.0.8/30",
+                "10.0.0.12/31",
+                "10.0.0.14/32",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unaligned_start_to_end_of_space() {
+        assert_eq!(
+            range_to_cidrs(addr("255.255.255.254"), u32::MAX),
+            cidrs(&["255.255.255.254/31"])
+        );
+        assert_eq!(
+            range_to_cidrs(1, u32::MAX).len(),
+            32,
+            "one block per bit for the range starting at 1"
+        );
+    }
+
+    #[test]
+    fn test_blocks_are_contiguous() {
+        let start = addr("3.7.11.13");
+        let end = addr("200.100.50.25");
+        let blocks = range_to_cidrs(start, end);
+
+        let mut next = start as u64;
+        for block in blocks.iter() {
+            assert_eq!(u32::from(block.first_address()) as u64, next);
+            next = u32::from(block.last_address()) as u64 + 1;
+        }
+        assert_eq!(next, end as u64 + 1);
+    }
+
+    #[test]
+    fn test_empty_when_start_after_end() {
+        assert!(range_to_cidrs(10, 9).is_empty());
+    }
+
+    #[test]
+    fn test_reserved_ipv4() {
+        for ip in [
+            "10.1.2.3",
+            "127.0.0.1",
+            "169.254.0.1",
+            "192.168.1.1",
+            "0.0.0.0",
+            "100.64.0.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(is_reserved_ipv4(&ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in ["1.1.1.1", "8.8.8.8", "100.128.0.1", "172.32.0.1"] {
+            assert!(!is_reserved_ipv4(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_reserved_ipv6() {
+        for ip in ["::1", "::", "fe80::1", "fd00::1", "ff02::1", "2001:db8::1"] {
+            assert!(is_reserved_ipv6(&ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in ["2001:4860:4860::8888", "2606:4700::1111"] {
+            assert!(!is_reserved_ipv6(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_embedded_ipv4() {
+        let cases = [
+            ("::ffff:1.2.3.4", Some("1.2.3.4")),
+            ("::ffff:0:0", Some("0.0.0.0")),
+            ("::1.2.3.4", Some("1.2.3.4")),
+            ("::", None),
+            ("::1", None),
+            ("2001:4860:4860::8888", None),
+            ("64:ff9b::1.2.3.4", None),
+        ];
+        for (ip, expected) in cases {
+            assert_eq!(
+                embedded_ipv4(&ip.parse().unwrap()),
+                expected.map(|value| value.parse().unwrap()),
+                "{}",
+                ip
+            );
+        }
+    }
+}
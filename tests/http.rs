@@ -0,0 +1,566 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use ipgeod::{
+    api::{
+        post_ipv4_batch_stream, Api, Dataset, DatasetFile, NotFoundStatus, MAX_BATCH_SIZE,
+        X_DATA_UPDATED,
+    },
+    metrics::Metrics,
+    providers::{CountryFilter, HerrbischoffProvider},
+    CountryCode, IpgeoProvider,
+};
+use poem::{
+    http::{header, StatusCode},
+    post,
+    test::TestClient,
+    Endpoint, EndpointExt,
+};
+use poem_openapi::OpenApiService;
+use serde_json::json;
+
+fn api(not_found_status: NotFoundStatus) -> Api {
+    let provider = HerrbischoffProvider::from_bytes(
+        &[
+            ("us", b"1.0.0.0/24\n".as_slice()),
+            ("au", b"1.0.2.0/24\n".as_slice()),
+        ],
+        &[("de", b"2a00::/16\n".as_slice())],
+        &CountryFilter::default(),
+    )
+    .unwrap();
+
+    Api::new(
+        Arc::new(ArcSwap::from_pointee(Dataset {
+            provider: IpgeoProvider::Herrbischoff(provider),
+            files: vec![DatasetFile {
+                path: PathBuf::from("country-ip-blocks"),
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            }],
+        })),
+        Arc::new(Metrics::new().unwrap()),
+        None,
+        None,
+        false,
+        not_found_status,
+        1 << 24,
+    )
+}
+
+fn client(not_found_status: NotFoundStatus) -> TestClient<impl Endpoint> {
+    TestClient::new(OpenApiService::new(
+        api(not_found_status),
+        "ipgeod",
+        env!("CARGO_PKG_VERSION"),
+    ))
+}
+
+fn error(code: u32, message: &str) -> serde_json::Value {
+    json!({ "code": code, "message": message })
+}
+
+#[tokio::test]
+async fn test_lookup_status_codes() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let cases = [
+        ("/ipv4/1.0.0.1", StatusCode::OK),
+        ("/ipv4/1.0.2.255", StatusCode::OK),
+        ("/ipv4/1.0.1.1", StatusCode::NOT_FOUND),
+        ("/ipv4/10.0.0.1", StatusCode::NOT_FOUND),
+        ("/ipv4/1.0.0", StatusCode::BAD_REQUEST),
+        ("/ipv4/2a00::1", StatusCode::BAD_REQUEST),
+        ("/ipv6/2a00::1", StatusCode::OK),
+        ("/ipv6/::ffff:1.0.0.1", StatusCode::OK),
+        ("/ipv6/2a01::1", StatusCode::NOT_FOUND),
+        ("/ipv6/fe80::1", StatusCode::NOT_FOUND),
+        ("/ipv6/1.0.0.1", StatusCode::BAD_REQUEST),
+    ];
+    for (path, status) in cases {
+        assert_eq!(client.get(path).send().await.0.status(), status, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_lookup_bodies() {
+    let client = client(NotFoundStatus::NotFound);
+
+    client
+        .get("/ipv4/1.0.0.1")
+        .send()
+        .await
+        .assert_json(json!({
+            "country": "US",
+            "country_numeric": 840,
+            "country_name": "United States of America",
+            "continent": "NA",
+            "continent_name": "North America",
+            "source": "herrbischoff",
+        }))
+        .await;
+    client
+        .get("/ipv6/2a00::1")
+        .send()
+        .await
+        .assert_json(json!({
+            "country": "DE",
+            "country_numeric": 276,
+            "country_name": "Germany",
+            "continent": "EU",
+            "continent_name": "Europe",
+            "source": "herrbischoff",
+        }))
+        .await;
+
+    client
+        .get("/ipv4/not-an-ip")
+        .send()
+        .await
+        .assert_json(error(100, "Invalid IP address"))
+        .await;
+    client
+        .get("/ipv4/1.0.1.1")
+        .send()
+        .await
+        .assert_json(error(101, "IP address not covered in database"))
+        .await;
+    client
+        .get("/ipv4/1.0.1.1?debug=true")
+        .send()
+        .await
+        .assert_json(json!({
+            "code": 101,
+            "message": "IP address not covered in database",
+            "outcome": "in_gap",
+        }))
+        .await;
+    client
+        .get("/ipv4/10.0.0.1")
+        .send()
+        .await
+        .assert_json(error(
+            107,
+            "IP address is reserved for special use and not publicly routable",
+        ))
+        .await;
+}
+
+#[tokio::test]
+async fn test_lookup_plain_text() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let resp = client
+        .get("/ipv4/1.0.0.1")
+        .header(header::ACCEPT, "text/plain")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text("US\n").await;
+
+    let resp = client
+        .get("/ipv4/1.0.1.1")
+        .header(header::ACCEPT, "text/plain")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    resp.assert_text("IP address not covered in database\n")
+        .await;
+}
+
+#[tokio::test]
+async fn test_lookup_geojson() {
+    let client = client(NotFoundStatus::NotFound);
+
+    // The dataset has no coordinates
+    let resp = client
+        .get("/ipv4/1.0.0.1")
+        .query("format", &"geojson")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_content_type("application/geo+json");
+    resp.assert_json(json!({
+        "type": "Feature",
+        "geometry": null,
+        "properties": {
+            "country": "US",
+            "country_numeric": 840,
+            "country_name": "United States of America",
+            "continent": "NA",
+            "continent_name": "North America",
+            "source": "herrbischoff",
+        },
+    }))
+    .await;
+
+    // Overrides the `Accept` header
+    let resp = client
+        .get("/ipv4/1.0.0.1")
+        .header(header::ACCEPT, "text/plain")
+        .query("format", &"json")
+        .send()
+        .await;
+    resp.assert_content_type("application/json; charset=utf-8");
+}
+
+#[tokio::test]
+async fn test_lookup_integer() {
+    let client = client(NotFoundStatus::NotFound);
+
+    // 1.0.0.1
+    let resp = client.get("/ipv4/int/16777217").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!({
+        "country": "US",
+        "country_numeric": 840,
+        "country_name": "United States of America",
+        "continent": "NA",
+        "continent_name": "North America",
+        "source": "herrbischoff",
+    }))
+    .await;
+
+    let cases = [
+        // 1.0.1.1
+        ("16777473", StatusCode::NOT_FOUND),
+        // 10.0.0.1
+        ("167772161", StatusCode::NOT_FOUND),
+        ("4294967296", StatusCode::BAD_REQUEST),
+        ("-1", StatusCode::BAD_REQUEST),
+        ("1.0.0.1", StatusCode::BAD_REQUEST),
+    ];
+    for (value, status) in cases {
+        client
+            .get(format!("/ipv4/int/{}", value))
+            .send()
+            .await
+            .assert_status(status);
+    }
+}
+
+#[tokio::test]
+async fn test_not_found_status() {
+    let resp = client(NotFoundStatus::NoContent)
+        .get("/ipv4/1.0.1.1")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NO_CONTENT);
+    resp.assert_bytes([]).await;
+
+    let resp = client(NotFoundStatus::Ok).get("/ipv4/1.0.1.1").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!({
+        "country": null,
+        "country_numeric": null,
+        "country_name": null,
+        "continent": null,
+        "continent_name": null,
+        "source": null,
+    }))
+    .await;
+
+    // Invalid addresses are still rejected
+    client(NotFoundStatus::Ok)
+        .get("/ipv4/not-an-ip")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_default_country() {
+    let client = TestClient::new(OpenApiService::new(
+        api(NotFoundStatus::NotFound).with_default_country(CountryCode::new("XX")),
+        "ipgeod",
+        env!("CARGO_PKG_VERSION"),
+    ));
+
+    let resp = client
+        .get("/ipv4/1.0.1.1")
+        .query("case", &"lower")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!({
+        "country": "xx",
+        "country_numeric": null,
+        "country_name": null,
+        "continent": null,
+        "continent_name": null,
+        "source": null,
+        "is_default": true,
+    }))
+    .await;
+
+    // Real matches aren't flagged
+    let resp = client.get("/ipv4/1.0.0.1").send().await;
+    resp.assert_status_is_ok();
+    assert!(resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get_opt("is_default")
+        .is_none());
+
+    // Invalid and reserved addresses are still rejected
+    client
+        .get("/ipv4/not-an-ip")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    client
+        .get("/ipv4/10.0.0.1")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_country_code_alias() {
+    let alias_client = TestClient::new(OpenApiService::new(
+        api(NotFoundStatus::NotFound).with_country_code_alias(true),
+        "ipgeod",
+        env!("CARGO_PKG_VERSION"),
+    ));
+
+    let resp = alias_client
+        .get("/ipv4/1.0.0.1")
+        .query("case", &"lower")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let object = json.value().object();
+    object.get("country").assert_string("us");
+    object.get("country_code").assert_string("us");
+
+    // Left out unless enabled
+    let resp = client(NotFoundStatus::NotFound)
+        .get("/ipv4/1.0.0.1")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    assert!(resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get_opt("country_code")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_data_updated_header() {
+    let client = client(NotFoundStatus::NoContent);
+
+    for path in ["/ipv4/1.0.0.1", "/ipv4/1.0.1.1", "/ipv4/not-an-ip"] {
+        client
+            .get(path)
+            .send()
+            .await
+            .assert_header(X_DATA_UPDATED, "Tue, 14 Nov 2023 22:13:20 GMT");
+    }
+}
+
+#[tokio::test]
+async fn test_ipv4_summary() {
+    let client = client(NotFoundStatus::NotFound);
+
+    // US and AU cover as many addresses, so the tie goes to AU
+    let resp = client.get("/ipv4/summary/8").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!({
+        "prefix_len": 8,
+        "subnets": 256,
+        "uncovered_subnets": 255,
+        "countries": [{ "country": "AU", "subnets": 1 }],
+    }))
+    .await;
+
+    let resp = client.get("/ipv4/summary/24").send().await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_json(error(
+        108,
+        "Summary prefix length must be between /0 and /16",
+    ))
+    .await;
+
+    client
+        .get("/ipv4/summary/abc")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_all_countries() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let resp = client.get("/ipv4/1.0.0.1/all").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!(["US"])).await;
+
+    let resp = client.get("/ipv4/1.0.1.1/all").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!([])).await;
+
+    client
+        .get("/ipv4/abc/all")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_is_country() {
+    let client = client(NotFoundStatus::NotFound);
+
+    for (path, is_match) in [
+        ("/ipv4/1.0.0.1/is/US", true),
+        ("/ipv4/1.0.0.1/is/us", true),
+        ("/ipv4/1.0.0.1/is/AU", false),
+        ("/ipv4/1.0.1.1/is/US", false),
+        ("/ipv4/127.0.0.1/is/US", false),
+    ] {
+        let resp = client.get(path).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_json(json!({ "match": is_match })).await;
+    }
+
+    for path in ["/ipv4/abc/is/US", "/ipv4/1.0.0.1/is/USA"] {
+        client
+            .get(path)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_ipv4_range_countries() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let resp = client.get("/ipv4/range/1.0.0.128/1.0.2.0").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!([
+        { "country": "US", "start": "1.0.0.128", "end": "1.0.0.255" },
+        { "country": "AU", "start": "1.0.2.0", "end": "1.0.2.0" },
+    ]))
+    .await;
+
+    let resp = client.get("/ipv4/range/1.0.2.0/1.0.0.0").send().await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_json(error(109, "Range start must not be after its end"))
+        .await;
+
+    let resp = client.get("/ipv4/range/1.0.0.0/2.0.0.0").send().await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_json(error(110, "Range exceeds the limit of 16777216 addresses"))
+        .await;
+
+    client
+        .get("/ipv4/range/1.0.0.0/abc")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_conditional_lookup() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let resp = client.get("/ipv4/1.0.0.1").send().await;
+    resp.assert_status_is_ok();
+    let etag = resp.0.headers()[header::ETAG].to_str().unwrap().to_owned();
+    assert!(etag.starts_with("W/\""), "{}", etag);
+
+    let resp = client
+        .get("/ipv4/1.0.0.1")
+        .header(header::IF_NONE_MATCH, format!("\"other\", {}", etag))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_MODIFIED);
+    resp.assert_header(header::ETAG, etag.as_str());
+    resp.assert_bytes([]).await;
+
+    // Other addresses and representations are tagged differently
+    for req in [
+        client.get("/ipv4/1.0.0.2"),
+        client
+            .get("/ipv4/1.0.0.1")
+            .header(header::ACCEPT, "text/plain"),
+    ] {
+        let resp = req
+            .header(header::IF_NONE_MATCH, etag.as_str())
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        assert_ne!(resp.0.headers()[header::ETAG], etag.as_str());
+    }
+
+    // Errors are never cached
+    let resp = client
+        .get("/ipv4/1.0.1.1")
+        .header(header::IF_NONE_MATCH, "*")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    assert!(!resp.0.headers().contains_key(header::ETAG));
+}
+
+#[tokio::test]
+async fn test_batch_lookup() {
+    let client = client(NotFoundStatus::NotFound);
+
+    let resp = client
+        .post("/ipv4/batch")
+        .body_json(&json!(["1.0.0.1", "1.0.1.1", "not-an-ip"]))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!([
+        { "ip_address": "1.0.0.1", "country": "US", "error_code": null },
+        { "ip_address": "1.0.1.1", "country": null, "error_code": 101 },
+        { "ip_address": "not-an-ip", "country": null, "error_code": 100 },
+    ]))
+    .await;
+
+    client
+        .post("/ipv4/batch")
+        .body_json(&vec!["1.0.0.1"; MAX_BATCH_SIZE + 1])
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_batch_lookup_stream() {
+    let client = TestClient::new(post(post_ipv4_batch_stream).data(api(NotFoundStatus::NotFound)));
+
+    let resp = client
+        .post("/")
+        .body("1.0.0.1\n\n not-an-ip \n1.0.1.1")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_content_type("application/x-ndjson");
+
+    let body = resp.0.into_body().into_string().await.unwrap();
+    let lines = body
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            json!({ "ip_address": "1.0.0.1", "country": "US", "error_code": null }),
+            json!({ "ip_address": "not-an-ip", "country": null, "error_code": 100 }),
+            json!({ "ip_address": "1.0.1.1", "country": null, "error_code": 101 }),
+        ]
+    );
+}